@@ -0,0 +1,62 @@
+// Run with `cargo bench`, or `cargo bench -- gravity_tick` for a single group. Criterion keeps the results of the
+// last run in target/criterion and reports the change against them, so run once before and once after a change
+use criterion::{criterion_group, criterion_main, BatchSize, BenchmarkId, Criterion};
+use frame_buffer::simulation::{CollisionMode, PhysicsBody, Simulation};
+use rand::rngs::StdRng;
+use rand::SeedableRng;
+
+const BODY_COUNTS: [usize; 3] = [10, 100, 1000];
+// Ticks run per iteration, each iteration starts from a fresh copy of the same simulation
+const TICKS: usize = 10;
+const SEED: u64 = 0;
+
+// ----------------------------------------------------------------
+
+fn simulation(num_of_bodies: usize) -> Simulation {
+    let mut rng = StdRng::seed_from_u64(SEED);
+    let bodies = (0..num_of_bodies)
+        .map(|_| PhysicsBody::new_rand_with(&mut rng))
+        .collect();
+    Simulation::new(bodies, None, None, None, CollisionMode::Collide)
+}
+
+fn bench_ticks(c: &mut Criterion, name: &str, tick: impl Fn(&mut Simulation)) {
+    let mut group = c.benchmark_group(name);
+    for num_of_bodies in BODY_COUNTS {
+        let simulation = simulation(num_of_bodies);
+        group.bench_with_input(
+            BenchmarkId::from_parameter(num_of_bodies),
+            &simulation,
+            |b, simulation| {
+                b.iter_batched(
+                    || simulation.clone(),
+                    |mut simulation| {
+                        (0..TICKS).for_each(|_| tick(&mut simulation));
+                        simulation
+                    },
+                    BatchSize::SmallInput,
+                )
+            },
+        );
+    }
+    group.finish();
+}
+
+fn gravity_tick(c: &mut Criterion) {
+    bench_ticks(c, "gravity_tick", |x| x.gravity_tick(1.0))
+}
+
+fn collision_tick(c: &mut Criterion) {
+    bench_ticks(c, "collision_tick", |x| {
+        x.collision_tick();
+    })
+}
+
+fn physics_tick(c: &mut Criterion) {
+    bench_ticks(c, "physics_tick", |x| {
+        x.physics_tick();
+    })
+}
+
+criterion_group!(benches, gravity_tick, collision_tick, physics_tick);
+criterion_main!(benches);
@@ -0,0 +1,85 @@
+use super::simulation::*;
+
+use rodio::buffer::SamplesBuffer;
+use rodio::{OutputStream, OutputStreamHandle};
+use std::time::{Duration, Instant};
+use vector2d::Vector2D;
+
+const SAMPLE_RATE: u32 = 44100;
+// Seconds
+const CLICK_LENGTH: f32 = 0.04;
+// Hz
+const CLICK_PITCH: f32 = 900.0;
+// Per second, the click is mostly gone after a quarter of its length
+const CLICK_DECAY: f32 = 120.0;
+// Collisions at least this energetic click at full volume, quieter ones by the square root of their share of it
+const LOUD_ENERGY: f32 = 50.0;
+// Clicks that would be quieter than this aren't played
+const MIN_VOLUME: f32 = 0.02;
+const MAX_AMPLITUDE: f32 = 0.5;
+const MIN_CLICK_INTERVAL: Duration = Duration::from_millis(50);
+
+// ----------------------------------------------------------------
+
+// Clicks for collisions, panned to where on screen they happened. A pile-up gets a single click for its hardest
+// collision, and clicks never come closer together than MIN_CLICK_INTERVAL
+pub struct CollisionSounds {
+    // Nothing plays once the stream is dropped
+    _stream: OutputStream,
+    handle: OutputStreamHandle,
+    last_click: Option<Instant>,
+}
+
+#[allow(dead_code)]
+impl CollisionSounds {
+    // Constructor
+    // None if there's no audio device, the simulation runs silently then
+    pub fn new() -> Option<CollisionSounds> {
+        match OutputStream::try_default() {
+            Ok((stream, handle)) => Some(CollisionSounds {
+                _stream: stream,
+                handle,
+                last_click: None,
+            }),
+            Err(e) => {
+                eprintln!("Unable to play sounds: {e}");
+                None
+            }
+        }
+    }
+
+    // Methods
+    // pan maps a collision's position to where it is heard, from 0.0 fully left to 1.0 fully right
+    pub fn play(&mut self, collisions: &[CollisionEvent], pan: impl Fn(Vector2D<Scalar>) -> f32) {
+        if self.last_click.is_some_and(|x| x.elapsed() < MIN_CLICK_INTERVAL) {
+            return;
+        }
+        let loudest = match collisions.iter().max_by(|a, b| a.energy().total_cmp(&b.energy())) {
+            Some(v) => v,
+            None => return,
+        };
+        let volume = (to_f32(loudest.energy()) / LOUD_ENERGY).sqrt().min(1.0);
+        if volume < MIN_VOLUME {
+            return;
+        }
+        match self.handle.play_raw(click(volume, pan(loudest.pos).clamp(0.0, 1.0))) {
+            Ok(()) => self.last_click = Some(Instant::now()),
+            Err(e) => eprintln!("Unable to play sound: {e}"),
+        }
+    }
+}
+
+// A decaying sine, in stereo
+fn click(volume: f32, pan: f32) -> SamplesBuffer<f32> {
+    // Equal power panning, so a click sounds as loud in the middle as at the sides
+    let (left, right) = ((1.0 - pan).sqrt(), pan.sqrt());
+    let samples = (0..(CLICK_LENGTH * SAMPLE_RATE as f32) as usize)
+        .flat_map(|i| {
+            let t = i as f32 / SAMPLE_RATE as f32;
+            let sample = (std::f32::consts::TAU * CLICK_PITCH * t).sin() * (-CLICK_DECAY * t).exp();
+            let sample = sample * volume * MAX_AMPLITUDE;
+            [sample * left, sample * right]
+        })
+        .collect::<Vec<_>>();
+    SamplesBuffer::new(2, SAMPLE_RATE, samples)
+}
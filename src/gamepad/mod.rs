@@ -0,0 +1,81 @@
+#[cfg(feature = "gamepad")]
+use gilrs::{Axis, Button, Event, EventType, Gilrs};
+use vector2d::Vector2D;
+
+// Sticks pushed less than this far count as centered
+#[cfg(feature = "gamepad")]
+const DEADZONE: f32 = 0.3;
+
+// ----------------------------------------------------------------
+
+// What the gamepads asked for this frame, the window loop merges it with the keyboard and mouse. Everything is off
+// without the gamepad feature or with no gamepad connected
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct GamepadInput {
+    pub move_up: bool,
+    pub move_down: bool,
+    pub move_right: bool,
+    pub move_left: bool,
+    pub zoom_in: bool,
+    pub zoom_out: bool,
+    pub add_body: bool,
+    pub remove_body: bool,
+    // Set while the place button is held, the right stick from -1 to 1 on both axes with y pointing down
+    pub aim: Option<Vector2D<f32>>,
+}
+
+// ----------------------------------------------------------------
+
+// Left stick pans, the triggers zoom, South adds a body, East removes one and holding West places a body aimed with
+// the right stick. Every connected gamepad is listened to
+#[cfg(feature = "gamepad")]
+pub struct Gamepad {
+    gilrs: Gilrs,
+}
+
+#[cfg(feature = "gamepad")]
+#[allow(dead_code)]
+impl Gamepad {
+    // Constructor
+    // None if gamepads can't be used on this platform, the keyboard and mouse still work then
+    pub fn new() -> Option<Gamepad> {
+        match Gilrs::new() {
+            Ok(gilrs) => Some(Gamepad { gilrs }),
+            Err(e) => {
+                eprintln!("Unable to use gamepads: {e}");
+                None
+            }
+        }
+    }
+
+    // Methods
+    // Presses come from the events since the last call so one between two frames isn't missed, held buttons and
+    // sticks from the state the events leave behind
+    pub fn poll(&mut self) -> GamepadInput {
+        let mut input = GamepadInput::default();
+        while let Some(Event { event, .. }) = self.gilrs.next_event() {
+            match event {
+                EventType::ButtonPressed(Button::South, _) => input.add_body = true,
+                EventType::ButtonPressed(Button::East, _) => input.remove_body = true,
+                _ => {}
+            }
+        }
+        for (_, gamepad) in self.gilrs.gamepads() {
+            // Up is positive on the sticks and negative on screen
+            let (left_x, left_y) = (gamepad.value(Axis::LeftStickX), gamepad.value(Axis::LeftStickY));
+            input.move_up |= left_y > DEADZONE;
+            input.move_down |= left_y < -DEADZONE;
+            input.move_right |= left_x > DEADZONE;
+            input.move_left |= left_x < -DEADZONE;
+            input.zoom_in |= gamepad.is_pressed(Button::RightTrigger2);
+            input.zoom_out |= gamepad.is_pressed(Button::LeftTrigger2);
+            if gamepad.is_pressed(Button::West) {
+                input.aim = Some(Vector2D::new(
+                    gamepad.value(Axis::RightStickX),
+                    -gamepad.value(Axis::RightStickY),
+                ));
+            }
+        }
+        input
+    }
+}
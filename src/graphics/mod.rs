@@ -1,7 +1,43 @@
+mod font;
+
+use serde::{Deserialize, Serialize};
 use std::default;
 use std::fmt;
+use std::ops::Range;
 use vector2d::Vector2D;
 
+pub const TEXT_SCALE: u32 = 2;
+// Space between the text and the edge of a text panel
+const PANEL_PADDING: u32 = 4;
+pub const DEFAULT_MIN_BODY_RADIUS: f32 = 2.0;
+pub const DEFAULT_GRID_SPACING: f32 = 100.0;
+// Grid lines are never drawn closer together than this many pixels
+const MIN_GRID_LINE_DISTANCE: f32 = 20.0;
+pub const DEFAULT_FIELD_DENSITY: u32 = 40;
+// Every field sample costs a pass over all bodies, so the density is capped
+pub const MAX_FIELD_DENSITY: u32 = 200;
+// Fraction of the distance between field samples the longest arrows cover
+const FIELD_ARROW_FILL: f32 = 0.8;
+// Side of the square minimap in the top right corner, and the gap kept both around it and inside it, in pixels
+const MINIMAP_SIZE: f32 = 160.0;
+const MINIMAP_MARGIN: f32 = 8.0;
+// Pixels with a channel at least this bright glow, the blur radius is in pixels of the half resolution copy
+const BLOOM_THRESHOLD: u8 = 160;
+const BLOOM_RADIUS: usize = 4;
+const BLOOM_STRENGTH: f32 = 0.8;
+// Fraction of the fitted area's size left empty on each side by Scene::fit_bounds
+const FIT_MARGIN: f32 = 0.05;
+
+// ----------------------------------------------------------------
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum BlendMode {
+    Replace,
+    Additive,
+    // Mixed with what is already drawn, 0.0 leaves it as it is and 1.0 is the same as Replace
+    Alpha(f32),
+}
+
 // ----------------------------------------------------------------
 
 pub trait Draw {
@@ -10,9 +46,94 @@ pub trait Draw {
 
     fn offset(&self, offset_by: Vector2D<f32>) -> Box<dyn Draw>;
     fn scale(&self, times: f32) -> Box<dyn Draw>;
+    fn scale_with_min_size(&self, times: f32, _min_size: f32) -> Box<dyn Draw> {
+        self.scale(times)
+    }
+
+    // index is where the element is in the document, for elements that need an id unique within it
+    fn to_svg_element(&self, index: usize) -> String;
 
     fn z_index(&self) -> u32;
     fn set_z_index(&mut self, val: u32);
+
+    // Only thin lines are drawn antialiased, other shapes ignore this
+    fn set_antialias(&mut self, _val: bool) {}
+}
+
+// ----------------------------------------------------------------
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub enum ThemeName {
+    Dark,
+    Light,
+    HighContrast,
+    RetroAmber,
+}
+
+#[allow(dead_code)]
+impl ThemeName {
+    // Methods
+    pub fn next(self) -> ThemeName {
+        match self {
+            ThemeName::Dark => ThemeName::Light,
+            ThemeName::Light => ThemeName::HighContrast,
+            ThemeName::HighContrast => ThemeName::RetroAmber,
+            ThemeName::RetroAmber => ThemeName::Dark,
+        }
+    }
+
+    pub fn theme(self) -> Theme {
+        match self {
+            ThemeName::Dark => Theme {
+                background: Color::new(0, 0, 0),
+                arrow: Color::new(255, 255, 255),
+                force_arrow: Color::new(255, 80, 80),
+                trail_brightness: 1.0,
+                grid: Color::new(40, 40, 40),
+                hud: Color::new(255, 255, 255),
+            },
+            ThemeName::Light => Theme {
+                background: Color::new(235, 235, 230),
+                arrow: Color::new(20, 20, 20),
+                force_arrow: Color::new(200, 30, 30),
+                trail_brightness: 0.7,
+                grid: Color::new(200, 200, 195),
+                hud: Color::new(20, 20, 20),
+            },
+            ThemeName::HighContrast => Theme {
+                background: Color::new(0, 0, 0),
+                arrow: Color::new(255, 255, 0),
+                force_arrow: Color::new(255, 0, 255),
+                trail_brightness: 1.0,
+                grid: Color::new(90, 90, 90),
+                hud: Color::new(0, 255, 255),
+            },
+            ThemeName::RetroAmber => Theme {
+                background: Color::new(20, 12, 0),
+                arrow: Color::new(255, 176, 0),
+                force_arrow: Color::new(255, 90, 0),
+                trail_brightness: 0.6,
+                grid: Color::new(60, 36, 0),
+                hud: Color::new(255, 176, 0),
+            },
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Theme {
+    pub background: Color,
+    pub arrow: Color,
+    pub force_arrow: Color,
+    pub trail_brightness: f32,
+    pub grid: Color,
+    pub hud: Color,
+}
+
+impl Default for Theme {
+    fn default() -> Self {
+        ThemeName::Dark.theme()
+    }
 }
 
 // ----------------------------------------------------------------
@@ -29,6 +150,10 @@ pub struct SceneUserInput {
 
     pub reset_view: bool,
 
+    // Dragging with this held moves the view with the cursor
+    pub mouse_pan: bool,
+
+    // In pixels, converted to world coordinates with the view as it is once the keys have moved it
     pub mouse_screen_pos: Option<Vector2D<f32>>,
     pub mouse_scroll_wheel: Option<f32>,
 }
@@ -40,6 +165,22 @@ pub struct Scene {
     scale: f32,
     min_max_scale: Option<Vector2D<f32>>,
     base_scale: f32,
+    min_body_radius: f32,
+    theme: Theme,
+    // Overrides the theme's background if set
+    background: Option<Color>,
+    antialias: bool,
+    show_grid: bool,
+    // In world units, see grid_spacing_for_scale
+    grid_spacing: f32,
+    show_field: bool,
+    // Field samples across the width of the screen
+    field_density: u32,
+    show_minimap: bool,
+    // Bright pixels get a blurred halo after drawing
+    bloom: bool,
+    // World point under the cursor when panning started
+    pan_grab: Option<Vector2D<f32>>,
 }
 
 #[allow(dead_code)]
@@ -53,6 +194,17 @@ impl Scene {
             scale: 1.0,
             min_max_scale,
             base_scale: (res.x as f32) / 500.0,
+            min_body_radius: DEFAULT_MIN_BODY_RADIUS,
+            theme: Theme::default(),
+            background: None,
+            antialias: false,
+            show_grid: false,
+            grid_spacing: DEFAULT_GRID_SPACING,
+            show_field: false,
+            field_density: DEFAULT_FIELD_DENSITY,
+            show_minimap: false,
+            bloom: false,
+            pan_grab: None,
         }
     }
 
@@ -73,6 +225,46 @@ impl Scene {
         &self.min_max_scale
     }
 
+    pub fn min_body_radius(&self) -> &f32 {
+        &self.min_body_radius
+    }
+
+    pub fn theme(&self) -> &Theme {
+        &self.theme
+    }
+
+    pub fn background(&self) -> &Color {
+        self.background.as_ref().unwrap_or(&self.theme.background)
+    }
+
+    pub fn antialias(&self) -> &bool {
+        &self.antialias
+    }
+
+    pub fn show_grid(&self) -> &bool {
+        &self.show_grid
+    }
+
+    pub fn grid_spacing(&self) -> &f32 {
+        &self.grid_spacing
+    }
+
+    pub fn show_field(&self) -> &bool {
+        &self.show_field
+    }
+
+    pub fn field_density(&self) -> &u32 {
+        &self.field_density
+    }
+
+    pub fn show_minimap(&self) -> &bool {
+        &self.show_minimap
+    }
+
+    pub fn bloom(&self) -> &bool {
+        &self.bloom
+    }
+
     // Mutable access
     pub fn contents_mut(&mut self) -> &mut Vec<Box<dyn Draw>> {
         &mut self.contents
@@ -98,6 +290,46 @@ impl Scene {
         self.min_max_scale = val
     }
 
+    pub fn set_min_body_radius(&mut self, val: f32) {
+        self.min_body_radius = val.abs()
+    }
+
+    pub fn set_theme(&mut self, val: Theme) {
+        self.theme = val
+    }
+
+    pub fn set_background(&mut self, val: Option<Color>) {
+        self.background = val
+    }
+
+    pub fn set_antialias(&mut self, val: bool) {
+        self.antialias = val
+    }
+
+    pub fn set_show_grid(&mut self, val: bool) {
+        self.show_grid = val
+    }
+
+    pub fn set_grid_spacing(&mut self, val: f32) {
+        self.grid_spacing = val.abs()
+    }
+
+    pub fn set_show_field(&mut self, val: bool) {
+        self.show_field = val
+    }
+
+    pub fn set_show_minimap(&mut self, val: bool) {
+        self.show_minimap = val
+    }
+
+    pub fn set_bloom(&mut self, val: bool) {
+        self.bloom = val
+    }
+
+    pub fn set_field_density(&mut self, val: u32) {
+        self.field_density = val.clamp(1, MAX_FIELD_DENSITY)
+    }
+
     // Methods
     pub fn change_scale(&mut self, amount: f32) {
         let scale_old = self.scale;
@@ -114,11 +346,46 @@ impl Scene {
     }
 
     pub fn focus_on(&mut self, p: Vector2D<f32>) {
-        self.offset = (Vector2D::new(self.res.x as f32, self.res.y as f32) / self.get_scale() / 2.0) - p
+        self.offset = self.focus_offset(p)
+    }
+
+    // Moves the offset a fraction of the way to the one focusing on p, 1.0 is the same as focus_on
+    pub fn focus_on_smooth(&mut self, p: Vector2D<f32>, factor: f32) {
+        let target = self.focus_offset(p);
+        self.offset += (target - self.offset) * factor.clamp(0.0, 1.0);
+    }
+
+    fn focus_offset(&self, p: Vector2D<f32>) -> Vector2D<f32> {
+        (Vector2D::new(self.res.x as f32, self.res.y as f32) / self.get_scale() / 2.0) - p
+    }
+
+    // Centers the area between min and max and zooms so all of it is on screen, as far as min_max_scale allows
+    pub fn fit_bounds(&mut self, min: Vector2D<f32>, max: Vector2D<f32>) {
+        let size = (max - min) * (1.0 + 2.0 * FIT_MARGIN);
+        if size.x > 0.0 || size.y > 0.0 {
+            // A side of length 0 gives an infinite ratio, so the other one decides
+            let fit_scale = (self.res.x as f32 / size.x).min(self.res.y as f32 / size.y);
+            self.set_scale(fit_scale / self.base_scale);
+        }
+        self.focus_on((min + max) / 2.0);
+    }
+
+    // The world point at the center of the screen stays there, base_scale follows the new width
+    pub fn resize(&mut self, new_res: Vector2D<u32>) {
+        let center = self.screen_to_world_coords(Vector2D::new(self.res.x as f32, self.res.y as f32) / 2.0);
+        self.res = new_res;
+        self.base_scale = (new_res.x as f32) / 500.0;
+        self.focus_on(center);
     }
 
+    // Keeps the world point `on` under the same screen pixel
     pub fn zoom_on(&mut self, amount: f32, on: Vector2D<f32>) {
-        unimplemented!()
+        let scale_old = self.get_scale();
+        self.set_scale(self.scale + amount);
+        if self.get_scale() != scale_old {
+            let on_screen = (on + self.offset) * scale_old;
+            self.offset = on_screen / self.get_scale() - on;
+        }
     }
 
     pub fn handle_user_input(&mut self, input: SceneUserInput) {
@@ -142,14 +409,21 @@ impl Scene {
             self.change_scale(-0.05)
         }
 
+        // The offset is moved so the grabbed point is under the cursor again
+        let mouse_world_pos = input.mouse_screen_pos.map(|x| self.screen_to_world_coords(x));
+        match (input.mouse_pan, mouse_world_pos) {
+            (true, Some(mouse_world_pos)) => match self.pan_grab {
+                Some(pan_grab) => self.offset += mouse_world_pos - pan_grab,
+                None => self.pan_grab = Some(mouse_world_pos),
+            },
+            _ => self.pan_grab = None,
+        }
+
         if let Some(mouse_screen_pos) = input.mouse_screen_pos {
             if let Some(mouse_scroll_wheel) = input.mouse_scroll_wheel {
-                self.change_scale(mouse_scroll_wheel)
-            } else {
+                self.zoom_on(mouse_scroll_wheel, self.screen_to_world_coords(mouse_screen_pos))
             }
-        } else {
         }
-        // TODO: MOUSE!!!
         if input.reset_view {
             self.offset = Vector2D::new(0.0, 0.0);
             self.scale = 1.0;
@@ -161,25 +435,185 @@ impl Scene {
     }
 
     pub fn draw(&self, frame_buffer: &mut FrameBuffer) {
-        self.contents
-            .iter()
-            .for_each(|shape| shape.offset(self.offset).scale(self.get_scale()).draw(frame_buffer));
+        let grid = if self.show_grid { self.grid_shapes() } else { vec![] };
+        grid.iter().chain(self.contents.iter()).for_each(|shape| {
+            let mut shape = shape
+                .offset(self.offset)
+                .scale_with_min_size(self.get_scale(), self.min_body_radius);
+            shape.set_antialias(self.antialias);
+            shape.draw(frame_buffer)
+        });
     }
 
     pub fn to_frame_buffer(&self) -> FrameBuffer {
         let mut output = FrameBuffer::new(self.res);
-        self.draw(&mut output);
+        self.render_into(&mut output);
 
         output
     }
 
+    // Same as to_frame_buffer but reuses frame_buffer, which is only reallocated if the resolution changed
+    pub fn render_into(&self, frame_buffer: &mut FrameBuffer) {
+        if *frame_buffer.size() == self.res {
+            frame_buffer.clear(*self.background());
+        } else {
+            *frame_buffer = FrameBuffer::new_filled(self.res, *self.background());
+        }
+        self.draw(frame_buffer);
+        if self.bloom {
+            frame_buffer.bloom();
+        }
+    }
+
+    // Shapes are emitted in contents order, call sort_contents first to keep z-order
+    pub fn to_svg(&self) -> String {
+        let mut out = format!(
+            "<svg xmlns=\"http://www.w3.org/2000/svg\" width=\"{0}\" height=\"{1}\" viewBox=\"0 0 {0} {1}\">\n",
+            self.res.x, self.res.y
+        );
+        out.push_str(&format!(
+            "<rect width=\"100%\" height=\"100%\" fill=\"{}\" />\n",
+            self.background().to_hex_string()
+        ));
+        self.contents.iter().enumerate().for_each(|(i, shape)| {
+            out.push_str(
+                &shape
+                    .offset(self.offset)
+                    .scale_with_min_size(self.get_scale(), self.min_body_radius)
+                    .to_svg_element(i),
+            );
+            out.push('\n');
+        });
+        out.push_str("</svg>\n");
+        out
+    }
+
     pub fn world_to_screen_coords(&self, pos: Vector2D<f32>) -> Vector2D<f32> {
-        unimplemented!();
+        (pos + self.offset) * self.get_scale()
     }
 
     pub fn screen_to_world_coords(&self, pos: Vector2D<f32>) -> Vector2D<f32> {
         (pos / self.get_scale()) - self.offset
     }
+
+    // Corners of the part of the world on screen, the smallest coordinates first
+    pub fn visible_world_rect(&self) -> (Vector2D<f32>, Vector2D<f32>) {
+        (
+            self.screen_to_world_coords(Vector2D::new(0.0, 0.0)),
+            self.screen_to_world_coords(Vector2D::new(self.res.x as f32, self.res.y as f32)),
+        )
+    }
+
+    // grid_spacing times the power of ten that puts lines at least MIN_GRID_LINE_DISTANCE pixels apart, so the grid
+    // gets coarser when zooming out and finer when zooming in
+    pub fn grid_spacing_for_scale(&self) -> f32 {
+        let on_screen = self.grid_spacing * self.get_scale();
+        if on_screen <= 0.0 || !on_screen.is_finite() {
+            return self.grid_spacing;
+        }
+        self.grid_spacing * 10.0_f32.powf((MIN_GRID_LINE_DISTANCE / on_screen).log10().ceil())
+    }
+
+    // Lines in world coordinates covering the visible area, below everything else
+    pub fn grid_shapes(&self) -> Vec<Box<dyn Draw>> {
+        let spacing = self.grid_spacing_for_scale();
+        let (min, max) = self.visible_world_rect();
+        let mut out: Vec<Box<dyn Draw>> = vec![];
+        if spacing <= 0.0 {
+            return out;
+        }
+        let mut x = (min.x / spacing).floor() * spacing;
+        while x <= max.x {
+            out.push(Box::new(Line::new(
+                Vector2D::new(x, min.y),
+                Vector2D::new(x, max.y),
+                0,
+                self.theme.grid,
+            )));
+            x += spacing;
+        }
+        let mut y = (min.y / spacing).floor() * spacing;
+        while y <= max.y {
+            out.push(Box::new(Line::new(
+                Vector2D::new(min.x, y),
+                Vector2D::new(max.x, y),
+                0,
+                self.theme.grid,
+            )));
+            y += spacing;
+        }
+        out
+    }
+
+    // World distance between field samples, they are spread evenly over the screen
+    pub fn field_sample_spacing(&self) -> f32 {
+        self.res.x as f32 / self.field_density as f32 / self.get_scale()
+    }
+
+    // Centers of a grid of cells covering the visible area, in world coordinates
+    pub fn field_sample_points(&self) -> Vec<Vector2D<f32>> {
+        let spacing = self.field_sample_spacing();
+        let (min, max) = self.visible_world_rect();
+        let (columns, rows) = (
+            ((max.x - min.x) / spacing).ceil() as usize,
+            ((max.y - min.y) / spacing).ceil() as usize,
+        );
+        (0..rows)
+            .flat_map(|y| (0..columns).map(move |x| Vector2D::new(x as f32 + 0.5, y as f32 + 0.5)))
+            .map(|x| min + x * spacing)
+            .collect()
+    }
+
+    // Longest a field arrow should be in world units so neighbouring arrows don't overlap
+    pub fn field_arrow_length(&self) -> f32 {
+        self.field_sample_spacing() * FIELD_ARROW_FILL
+    }
+
+    // Draws the points as dots on a square in the top right corner of the screen, together with a rectangle around
+    // the part of the world on screen. Both are fitted into the square, ignoring the offset and scale of the scene.
+    // points_extent is the smallest and largest corner of a rectangle holding every point, None without points
+    pub fn draw_minimap(
+        &self,
+        frame_buffer: &mut FrameBuffer,
+        points: &[(Vector2D<f32>, Color)],
+        points_extent: Option<(Vector2D<f32>, Vector2D<f32>)>,
+    ) {
+        let (view_min, view_max) = self.visible_world_rect();
+        let (min, max) = match points_extent {
+            Some((min, max)) => (
+                Vector2D::new(min.x.min(view_min.x), min.y.min(view_min.y)),
+                Vector2D::new(max.x.max(view_max.x), max.y.max(view_max.y)),
+            ),
+            None => (view_min, view_max),
+        };
+        let extent = max - min;
+
+        let corner = Vector2D::new(
+            frame_buffer.size().x as f32 - MINIMAP_SIZE - MINIMAP_MARGIN,
+            MINIMAP_MARGIN,
+        );
+        let inner_size = MINIMAP_SIZE - 2.0 * MINIMAP_MARGIN;
+        let scale = inner_size / extent.x.max(extent.y).max(f32::EPSILON);
+        // Centers the shorter side of the extent
+        let origin = corner
+            + Vector2D::new(MINIMAP_MARGIN, MINIMAP_MARGIN)
+            + (Vector2D::new(inner_size, inner_size) - extent * scale) / 2.0;
+        let to_minimap = |p: Vector2D<f32>| origin + (p - min) * scale;
+
+        let size = Vector2D::new(MINIMAP_SIZE, MINIMAP_SIZE);
+        frame_buffer.draw(&Rect::new(corner, size, 0, *self.background()));
+        frame_buffer.draw_outline(&Rect::new(corner, size, 0, self.theme.grid));
+        for (p, color) in points {
+            frame_buffer.draw(&Rect::new(to_minimap(*p), Vector2D::new(2.0, 2.0), 0, *color))
+        }
+        let view_corner = to_minimap(view_min);
+        frame_buffer.draw_outline(&Rect::new(
+            view_corner,
+            to_minimap(view_max) - view_corner,
+            0,
+            self.theme.hud,
+        ));
+    }
 }
 
 impl fmt::Debug for Scene {
@@ -204,8 +638,12 @@ pub struct FrameBuffer {
 impl FrameBuffer {
     // Constructor
     pub fn new(size: Vector2D<u32>) -> FrameBuffer {
+        FrameBuffer::new_filled(size, Color::new(0, 0, 0))
+    }
+
+    pub fn new_filled(size: Vector2D<u32>, color: Color) -> FrameBuffer {
         FrameBuffer {
-            buffer: vec![Color::new(0, 0, 0); (size.x * size.y) as usize],
+            buffer: vec![color; (size.x * size.y) as usize],
             size,
         }
     }
@@ -224,6 +662,10 @@ impl FrameBuffer {
         p.x >= 0.0 && p.x < (self.size.x as f32) && p.y >= 0.0 && p.y < (self.size.y as f32)
     }
 
+    pub fn clear(&mut self, color: Color) {
+        self.buffer.fill(color)
+    }
+
     pub fn set_pixel(&mut self, p: Vector2D<f32>, color: Color) {
         if self.contains_point(p) {
             let width = self.size.x as u32; // Dunno why I have to do that
@@ -231,6 +673,34 @@ impl FrameBuffer {
         }
     }
 
+    pub fn add_pixel(&mut self, p: Vector2D<f32>, color: Color) {
+        if self.contains_point(p) {
+            let i = (((p.y as u32) * self.size.x) + (p.x as u32)) as usize;
+            let old = self.buffer[i];
+            self.buffer[i] = Color::new(
+                old.r.saturating_add(color.r),
+                old.g.saturating_add(color.g),
+                old.b.saturating_add(color.b),
+            );
+        }
+    }
+
+    // Mixes color into the existing pixel, an alpha of 1.0 is the same as set_pixel
+    pub fn blend_pixel(&mut self, p: Vector2D<f32>, color: Color, alpha: f32) {
+        if self.contains_point(p) {
+            let i = (((p.y as u32) * self.size.x) + (p.x as u32)) as usize;
+            self.buffer[i] = self.buffer[i].lerp(color, alpha);
+        }
+    }
+
+    pub fn blend_pixel_with(&mut self, p: Vector2D<f32>, color: Color, mode: BlendMode) {
+        match mode {
+            BlendMode::Replace => self.set_pixel(p, color),
+            BlendMode::Additive => self.add_pixel(p, color),
+            BlendMode::Alpha(alpha) => self.blend_pixel(p, color, alpha),
+        }
+    }
+
     pub fn draw(&mut self, object: &impl Draw) {
         object.draw(self);
     }
@@ -239,14 +709,119 @@ impl FrameBuffer {
         object.draw_outline(self);
     }
 
+    pub fn draw_text(&mut self, pos: Vector2D<f32>, text: &str, color: Color) {
+        let mut cursor = pos;
+        for c in text.chars() {
+            if c == '\n' {
+                cursor = Vector2D::new(pos.x, cursor.y + ((font::GLYPH_HEIGHT + 2) * TEXT_SCALE) as f32);
+                continue;
+            }
+            for (y, row) in font::glyph(c).iter().enumerate() {
+                for x in 0..font::GLYPH_WIDTH {
+                    if row & (1 << (font::GLYPH_WIDTH - 1 - x)) != 0 {
+                        for sy in 0..TEXT_SCALE {
+                            for sx in 0..TEXT_SCALE {
+                                self.set_pixel(
+                                    Vector2D::new(
+                                        cursor.x + (x * TEXT_SCALE + sx) as f32,
+                                        cursor.y + (y as u32 * TEXT_SCALE + sy) as f32,
+                                    ),
+                                    color,
+                                )
+                            }
+                        }
+                    }
+                }
+            }
+            cursor.x += ((font::GLYPH_WIDTH + 1) * TEXT_SCALE) as f32;
+        }
+    }
+
+    // Text on an outlined box with its top left corner at pos, moved as needed to keep the whole box on screen
+    pub fn draw_text_panel(&mut self, pos: Vector2D<f32>, text: &str, color: Color, background: Color) {
+        let text_size = FrameBuffer::text_size(text);
+        let size = Vector2D::new(
+            (text_size.x + 2 * PANEL_PADDING) as f32,
+            (text_size.y + 2 * PANEL_PADDING) as f32,
+        );
+        let pos = Vector2D::new(
+            pos.x.min(self.size.x as f32 - size.x - 1.0).max(0.0),
+            pos.y.min(self.size.y as f32 - size.y - 1.0).max(0.0),
+        );
+        self.draw(&Rect::new(pos, size, 0, background));
+        self.draw_outline(&Rect::new(pos, size, 0, color));
+        self.draw_text(
+            pos + Vector2D::new(PANEL_PADDING as f32, PANEL_PADDING as f32),
+            text,
+            color,
+        );
+    }
+
+    // Every scale x scale square becomes a single pixel of their average color
+    pub fn downscaled(&self, scale: u32) -> FrameBuffer {
+        let scale = scale.max(1);
+        let size = Vector2D::new(self.size.x.div_ceil(scale), self.size.y.div_ceil(scale));
+        let mut buffer = Vec::with_capacity((size.x * size.y) as usize);
+        for y in 0..size.y {
+            for x in 0..size.x {
+                buffer.push(self.average_color(Vector2D::new(x * scale, y * scale), scale));
+            }
+        }
+        FrameBuffer { buffer, size }
+    }
+
+    // Average color of the square of side size starting at min, clipped to the buffer
+    pub fn average_color(&self, min: Vector2D<u32>, size: u32) -> Color {
+        let (mut sum, mut count) = ([0u32; 3], 0u32);
+        for y in min.y..(min.y + size).min(self.size.y) {
+            for x in min.x..(min.x + size).min(self.size.x) {
+                let color = self.buffer[(y * self.size.x + x) as usize];
+                sum[0] += color.r as u32;
+                sum[1] += color.g as u32;
+                sum[2] += color.b as u32;
+                count += 1;
+            }
+        }
+        if count == 0 {
+            return Color::new(0, 0, 0);
+        }
+        Color::new((sum[0] / count) as u8, (sum[1] / count) as u8, (sum[2] / count) as u8)
+    }
+
+    // Adds a blurred copy of the bright pixels back onto the buffer so they glow. The blur runs on a half resolution
+    // copy, a box blur along the rows and then along the columns
+    pub fn bloom(&mut self) {
+        let mut halo = self.downscaled(2);
+        halo.buffer.iter_mut().for_each(|x| {
+            if x.r.max(x.g).max(x.b) < BLOOM_THRESHOLD {
+                *x = Color::new(0, 0, 0)
+            }
+        });
+        let (width, height) = (halo.size.x as usize, halo.size.y as usize);
+        box_blur(&mut halo.buffer, width, height, 1, width);
+        box_blur(&mut halo.buffer, height, width, width, 1);
+        for y in 0..self.size.y {
+            for x in 0..self.size.x {
+                let glow = halo.buffer[((y / 2) * halo.size.x + x / 2) as usize].scaled(BLOOM_STRENGTH);
+                self.add_pixel(Vector2D::new(x as f32, y as f32), glow);
+            }
+        }
+    }
+
+    pub fn text_size(text: &str) -> Vector2D<u32> {
+        let lines = text.split('\n');
+        Vector2D::new(
+            lines.clone().map(|x| x.chars().count() as u32).max().unwrap_or(0) * (font::GLYPH_WIDTH + 1) * TEXT_SCALE,
+            lines.count() as u32 * (font::GLYPH_HEIGHT + 2) * TEXT_SCALE,
+        )
+    }
+
     // Casting
     pub fn to_vec_u8(&self, transparency: bool) -> Vec<u8> {
         let output_length = self.buffer.len();
         let mut output: Vec<u8> = Vec::with_capacity(output_length * (if transparency { 4 } else { 3 }));
 
-        for i in 0..(output_length * (if transparency { 4 } else { 3 })) {
-            let current_color = self.buffer[i / (if transparency { 4 } else { 3 })];
-
+        for current_color in &self.buffer {
             output.push(current_color.r);
             output.push(current_color.g);
             output.push(current_color.b);
@@ -260,11 +835,55 @@ impl FrameBuffer {
     pub fn to_vec_u32(&self) -> Vec<u32> {
         self.buffer.iter().map(|x| x.to_u32()).collect()
     }
+
+    #[cfg(feature = "native")]
+    pub fn save_png(&self, path: impl AsRef<std::path::Path>) -> std::io::Result<()> {
+        let file = std::io::BufWriter::new(std::fs::File::create(path)?);
+        let mut encoder = png::Encoder::new(file, self.size.x, self.size.y);
+        encoder.set_color(png::ColorType::Rgb);
+        encoder.set_depth(png::BitDepth::Eight);
+        encoder
+            .write_header()
+            .and_then(|mut writer| writer.write_image_data(&self.to_vec_u8(false)))
+            .map_err(std::io::Error::other)
+    }
+
+    // Like to_vec_u32 but reuses the allocation of out
+    pub fn write_u32(&self, out: &mut Vec<u32>) {
+        out.clear();
+        out.extend(self.buffer.iter().map(|x| x.to_u32()));
+    }
+
+    // Every character is an upper half block showing two pixels, with the top one as the foreground color and the
+    // bottom one as the background color. The buffer is scaled down by averaging until it fits in columns x rows
+    pub fn to_ansi_string(&self, columns: u32, rows: u32) -> String {
+        let scale = self
+            .size
+            .x
+            .div_ceil(columns.max(1))
+            .max(self.size.y.div_ceil(rows.max(1) * 2))
+            .max(1);
+        let (width, height) = (self.size.x.div_ceil(scale), self.size.y.div_ceil(scale * 2));
+
+        let mut out = String::new();
+        for y in 0..height {
+            for x in 0..width {
+                let top = self.average_color(Vector2D::new(x * scale, y * 2 * scale), scale);
+                let bottom = self.average_color(Vector2D::new(x * scale, (y * 2 + 1) * scale), scale);
+                out.push_str(&top.bg_string());
+                out.push_str(&bottom.background_string());
+                out.push('▀');
+            }
+            out.push_str(&Color::default_color());
+            out.push('\n');
+        }
+        out
+    }
 }
 
 // ----------------------------------------------------------------
 
-#[derive(Debug, Clone, Copy, PartialEq)]
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
 pub struct Color {
     pub r: u8,
     pub g: u8,
@@ -283,10 +902,33 @@ impl Color {
         ((self.r as u32) << 16) | ((self.g as u32) << 8) | (self.b as u32)
     }
 
+    // t of 0.0 gives self and 1.0 gives other
+    pub fn lerp(self, other: Color, t: f32) -> Color {
+        let t = t.clamp(0.0, 1.0);
+        let mix = |a: u8, b: u8| (a as f32 + (b as f32 - a as f32) * t).round() as u8;
+        Color::new(mix(self.r, other.r), mix(self.g, other.g), mix(self.b, other.b))
+    }
+
+    pub fn scaled(self, factor: f32) -> Color {
+        Color::new(
+            (self.r as f32 * factor).clamp(0.0, 255.0) as u8,
+            (self.g as f32 * factor).clamp(0.0, 255.0) as u8,
+            (self.b as f32 * factor).clamp(0.0, 255.0) as u8,
+        )
+    }
+
+    pub fn to_hex_string(self) -> String {
+        format!("#{:02x}{:02x}{:02x}", self.r, self.g, self.b)
+    }
+
     pub fn bg_string(self) -> String {
         format!("\x1b[38;2;{:?};{:?};{:?}m", self.r, self.g, self.b)
     }
 
+    pub fn background_string(self) -> String {
+        format!("\x1b[48;2;{:?};{:?};{:?}m", self.r, self.g, self.b)
+    }
+
     pub fn default_color() -> String {
         "\x1b[0m".to_string()
     }
@@ -300,6 +942,10 @@ pub struct Line {
     pos_2: Vector2D<f32>,
     color: Color,
     z_index: u32,
+    blend_mode: BlendMode,
+    // Width in pixels, measured perpendicular to the line
+    thickness: u32,
+    antialias: bool,
 }
 
 #[allow(dead_code)]
@@ -311,6 +957,9 @@ impl Line {
             pos_2,
             color,
             z_index,
+            blend_mode: BlendMode::Replace,
+            thickness: 1,
+            antialias: false,
         }
     }
 
@@ -327,6 +976,18 @@ impl Line {
         &self.color
     }
 
+    pub fn blend_mode(&self) -> &BlendMode {
+        &self.blend_mode
+    }
+
+    pub fn thickness(&self) -> &u32 {
+        &self.thickness
+    }
+
+    pub fn antialias(&self) -> &bool {
+        &self.antialias
+    }
+
     // Mutable access
     pub fn set_pos_1(&mut self, val: Vector2D<f32>) {
         self.pos_1 = val
@@ -339,18 +1000,191 @@ impl Line {
     pub fn set_color(&mut self, val: Color) {
         self.color = val
     }
+
+    pub fn set_blend_mode(&mut self, val: BlendMode) {
+        self.blend_mode = val
+    }
+
+    pub fn set_thickness(&mut self, val: u32) {
+        self.thickness = val.max(1)
+    }
+
+    // Methods
+    // Cohen-Sutherland, the part of the line inside the framebuffer grown by margin on every side or None if there
+    // isn't any
+    fn clipped(&self, size: Vector2D<u32>, margin: f32) -> Option<Line> {
+        const LEFT: u8 = 1;
+        const RIGHT: u8 = 2;
+        const TOP: u8 = 4;
+        const BOTTOM: u8 = 8;
+
+        // A pixel past every side so the ends still land on the same pixels as they would unclipped
+        let (min, max) = (
+            Vector2D::new(-1.0 - margin, -1.0 - margin),
+            Vector2D::new(size.x as f32 + margin, size.y as f32 + margin),
+        );
+        let outcode = |p: Vector2D<f32>| {
+            let mut code = 0;
+            if p.x < min.x {
+                code |= LEFT
+            } else if p.x > max.x {
+                code |= RIGHT
+            }
+            if p.y < min.y {
+                code |= TOP
+            } else if p.y > max.y {
+                code |= BOTTOM
+            }
+            code
+        };
+
+        let (mut p1, mut p2) = (self.pos_1, self.pos_2);
+        let (mut code_1, mut code_2) = (outcode(p1), outcode(p2));
+        loop {
+            if code_1 | code_2 == 0 {
+                return Some(Line {
+                    pos_1: p1,
+                    pos_2: p2,
+                    ..self.clone()
+                });
+            }
+            if code_1 & code_2 != 0 {
+                return None;
+            }
+            // Move an outside point onto the edge it is past, the other coordinate follows the line
+            let code = if code_1 != 0 { code_1 } else { code_2 };
+            let p = if code & TOP != 0 {
+                Vector2D::new(p1.x + (p2.x - p1.x) * (min.y - p1.y) / (p2.y - p1.y), min.y)
+            } else if code & BOTTOM != 0 {
+                Vector2D::new(p1.x + (p2.x - p1.x) * (max.y - p1.y) / (p2.y - p1.y), max.y)
+            } else if code & LEFT != 0 {
+                Vector2D::new(min.x, p1.y + (p2.y - p1.y) * (min.x - p1.x) / (p2.x - p1.x))
+            } else {
+                Vector2D::new(max.x, p1.y + (p2.y - p1.y) * (max.x - p1.x) / (p2.x - p1.x))
+            };
+            if code == code_1 {
+                p1 = p;
+                code_1 = outcode(p1);
+            } else {
+                p2 = p;
+                code_2 = outcode(p2);
+            }
+        }
+    }
+
+    // Walks along the longer axis and fills a span across the shorter one at every step, the span is as tall as the
+    // thickness divided by the cosine of the line's angle to the longer axis so the band has the right width
+    fn draw_thick(&self, frame_buffer: &mut FrameBuffer) {
+        let delta = self.pos_2 - self.pos_1;
+        let x_major = delta.x.abs() >= delta.y.abs();
+        // Coordinates are swapped for lines that are closer to vertical, so the rest only handles the x-major case
+        let swap = |v: Vector2D<f32>| if x_major { v } else { Vector2D::new(v.y, v.x) };
+        let (mut start, mut end) = (swap(self.pos_1), swap(self.pos_2));
+        if start.x > end.x {
+            std::mem::swap(&mut start, &mut end);
+        }
+        let run = end.x - start.x;
+        let (slope, half_span) = if run > 0.0 {
+            (
+                (end.y - start.y) / run,
+                self.thickness as f32 * delta.length() / run / 2.0,
+            )
+        } else {
+            (0.0, self.thickness as f32 / 2.0)
+        };
+
+        for x in start.x.round() as i32..=end.x.round() as i32 {
+            let y = start.y + (x as f32 - start.x) * slope;
+            for y in (y - half_span).round() as i32..(y + half_span).round() as i32 {
+                frame_buffer.blend_pixel_with(swap(Vector2D::new(x as f32, y as f32)), self.color, self.blend_mode);
+            }
+        }
+    }
+
+    // Xiaolin Wu's algorithm, every step along the longer axis covers the two pixels closest to the line across the
+    // shorter one in proportion to how close the line passes to their centers
+    fn draw_antialiased(&self, frame_buffer: &mut FrameBuffer) {
+        let steep = (self.pos_2.y - self.pos_1.y).abs() > (self.pos_2.x - self.pos_1.x).abs();
+        // Pixel centers are at half coordinates, moving them to whole ones keeps the rest like the textbook version
+        let swap = |v: Vector2D<f32>| {
+            let v = v - Vector2D::new(0.5, 0.5);
+            if steep {
+                Vector2D::new(v.y, v.x)
+            } else {
+                v
+            }
+        };
+        let (mut start, mut end) = (swap(self.pos_1), swap(self.pos_2));
+        if start.x > end.x {
+            std::mem::swap(&mut start, &mut end);
+        }
+        // f32::fract is negative for negative numbers
+        let fract = |v: f32| v - v.floor();
+        let run = end.x - start.x;
+        let gradient = if run > 0.0 { (end.y - start.y) / run } else { 1.0 };
+
+        let mut plot = |x: f32, y: f32, coverage: f32| {
+            let p = if steep {
+                Vector2D::new(y, x)
+            } else {
+                Vector2D::new(x, y)
+            };
+            match self.blend_mode {
+                BlendMode::Replace => frame_buffer.blend_pixel(p, self.color, coverage),
+                BlendMode::Additive => frame_buffer.add_pixel(p, self.color.scaled(coverage)),
+                BlendMode::Alpha(alpha) => frame_buffer.blend_pixel(p, self.color, coverage * alpha),
+            }
+        };
+
+        // The end pixels are only covered by the part of the line inside them
+        let mut end_pixel = |pos: Vector2D<f32>, x_gap: f32| -> (f32, f32) {
+            let x = pos.x.round();
+            let y = pos.y + gradient * (x - pos.x);
+            plot(x, y.floor(), (1.0 - fract(y)) * x_gap);
+            plot(x, y.floor() + 1.0, fract(y) * x_gap);
+            (x, y)
+        };
+        let (x_start, y_start) = end_pixel(start, 1.0 - fract(start.x + 0.5));
+        let (x_end, _) = end_pixel(end, fract(end.x + 0.5));
+
+        let mut y = y_start + gradient;
+        for x in (x_start as i32 + 1)..(x_end as i32) {
+            plot(x as f32, y.floor(), 1.0 - fract(y));
+            plot(x as f32, y.floor() + 1.0, fract(y));
+            y += gradient;
+        }
+    }
 }
 
 impl Draw for Line {
     fn draw(&self, frame_buffer: &mut FrameBuffer) {
-        let (mut x0, mut y0) = (self.pos_1.x as i32, self.pos_1.y as i32);
-        let (x1, y1) = (self.pos_2.x as i32, self.pos_2.y as i32);
+        // Thick and antialiased lines also touch pixels next to the line, so those are clipped a bit further out
+        let margin = match (self.thickness > 1, self.antialias) {
+            (true, _) => self.thickness as f32,
+            (false, true) => 1.0,
+            (false, false) => 0.0,
+        };
+        let line = match self.clipped(*frame_buffer.size(), margin) {
+            Some(v) => v,
+            None => return,
+        };
+        if line.thickness > 1 {
+            line.draw_thick(frame_buffer);
+            return;
+        }
+        if line.antialias {
+            line.draw_antialiased(frame_buffer);
+            return;
+        }
+
+        let (mut x0, mut y0) = (line.pos_1.x.floor() as i32, line.pos_1.y.floor() as i32);
+        let (x1, y1) = (line.pos_2.x.floor() as i32, line.pos_2.y.floor() as i32);
         let (dx, dy) = ((x1 - x0).abs(), -(y1 - y0).abs());
         let (sx, sy) = (if x0 < x1 { 1 } else { -1 }, if y0 < y1 { 1 } else { -1 });
         let mut error = dx + dy;
 
         loop {
-            frame_buffer.set_pixel(Vector2D::new(x0 as f32, y0 as f32), self.color);
+            frame_buffer.blend_pixel_with(Vector2D::new(x0 as f32, y0 as f32), self.color, self.blend_mode);
 
             if x0 == x1 && y0 == y1 {
                 break;
@@ -378,21 +1212,187 @@ impl Draw for Line {
     }
 
     fn offset(&self, offset_by: Vector2D<f32>) -> Box<dyn Draw> {
-        Box::new(Line::new(
-            self.pos_1 + offset_by,
-            self.pos_2 + offset_by,
-            self.z_index,
-            self.color,
-        ))
+        Box::new(Line {
+            pos_1: self.pos_1 + offset_by,
+            pos_2: self.pos_2 + offset_by,
+            ..self.clone()
+        })
     }
 
     fn scale(&self, times: f32) -> Box<dyn Draw> {
-        Box::new(Line::new(
-            Vector2D::new(self.pos_1.x * times, self.pos_1.y * times),
-            Vector2D::new(self.pos_2.x * times, self.pos_2.y * times),
-            self.z_index,
-            self.color,
-        ))
+        Box::new(Line {
+            pos_1: Vector2D::new(self.pos_1.x * times, self.pos_1.y * times),
+            pos_2: Vector2D::new(self.pos_2.x * times, self.pos_2.y * times),
+            ..self.clone()
+        })
+    }
+
+    fn to_svg_element(&self, _index: usize) -> String {
+        format!(
+            "<line x1=\"{}\" y1=\"{}\" x2=\"{}\" y2=\"{}\" stroke=\"{}\" stroke-width=\"{}\"{} />",
+            self.pos_1.x,
+            self.pos_1.y,
+            self.pos_2.x,
+            self.pos_2.y,
+            self.color.to_hex_string(),
+            self.thickness,
+            match self.blend_mode {
+                BlendMode::Replace => String::new(),
+                BlendMode::Additive => " style=\"mix-blend-mode:plus-lighter\"".to_string(),
+                BlendMode::Alpha(alpha) => format!(" stroke-opacity=\"{alpha}\""),
+            }
+        )
+    }
+
+    fn z_index(&self) -> u32 {
+        self.z_index
+    }
+
+    fn set_z_index(&mut self, val: u32) {
+        self.z_index = val
+    }
+
+    fn set_antialias(&mut self, val: bool) {
+        self.antialias = val
+    }
+}
+
+// ----------------------------------------------------------------
+
+// Connected line segments in one color, such as a body's trail. With fade_from set the oldest segment, the first,
+// starts in that color and they brighten to color at the last point
+#[derive(Debug, Clone, PartialEq)]
+pub struct Polyline {
+    points: Vec<Vector2D<f32>>,
+    color: Color,
+    fade_from: Option<Color>,
+    z_index: u32,
+    blend_mode: BlendMode,
+    antialias: bool,
+}
+
+#[allow(dead_code)]
+impl Polyline {
+    // Constructor
+    pub fn new(points: Vec<Vector2D<f32>>, z_index: u32, color: Color) -> Polyline {
+        Polyline {
+            points,
+            color,
+            fade_from: None,
+            z_index,
+            blend_mode: BlendMode::Replace,
+            antialias: false,
+        }
+    }
+
+    // Immutable access
+    pub fn points(&self) -> &Vec<Vector2D<f32>> {
+        &self.points
+    }
+
+    pub fn color(&self) -> &Color {
+        &self.color
+    }
+
+    pub fn fade_from(&self) -> &Option<Color> {
+        &self.fade_from
+    }
+
+    pub fn blend_mode(&self) -> &BlendMode {
+        &self.blend_mode
+    }
+
+    // Setters
+    pub fn set_color(&mut self, val: Color) {
+        self.color = val
+    }
+
+    pub fn set_fade_from(&mut self, val: Option<Color>) {
+        self.fade_from = val
+    }
+
+    pub fn set_blend_mode(&mut self, val: BlendMode) {
+        self.blend_mode = val
+    }
+
+    // Methods
+    pub fn segments(&self) -> impl Iterator<Item = Line> + '_ {
+        (1..self.points.len()).map(|i| {
+            let color = match self.fade_from {
+                Some(v) => v.lerp(self.color, i as f32 / (self.points.len() - 1) as f32),
+                None => self.color,
+            };
+            let mut segment = Line::new(self.points[i - 1], self.points[i], self.z_index, color);
+            segment.set_blend_mode(self.blend_mode);
+            segment.set_antialias(self.antialias);
+            segment
+        })
+    }
+}
+
+impl Draw for Polyline {
+    fn draw(&self, frame_buffer: &mut FrameBuffer) {
+        self.segments().for_each(|x| x.draw(frame_buffer));
+    }
+
+    fn draw_outline(&self, frame_buffer: &mut FrameBuffer) {
+        frame_buffer.draw(self);
+    }
+
+    fn offset(&self, offset_by: Vector2D<f32>) -> Box<dyn Draw> {
+        Box::new(Polyline {
+            points: self.points.iter().map(|x| *x + offset_by).collect(),
+            ..self.clone()
+        })
+    }
+
+    fn scale(&self, times: f32) -> Box<dyn Draw> {
+        Box::new(Polyline {
+            points: self
+                .points
+                .iter()
+                .map(|x| Vector2D::new(x.x * times, x.y * times))
+                .collect(),
+            ..self.clone()
+        })
+    }
+
+    // A fade becomes a gradient along the straight line from the first point to the last, which follows the segments
+    // closely enough for trails
+    fn to_svg_element(&self, index: usize) -> String {
+        let points = self
+            .points
+            .iter()
+            .map(|x| format!("{},{}", x.x, x.y))
+            .collect::<Vec<_>>()
+            .join(" ");
+        let blend = match self.blend_mode {
+            BlendMode::Replace => String::new(),
+            BlendMode::Additive => " style=\"mix-blend-mode:plus-lighter\"".to_string(),
+            BlendMode::Alpha(alpha) => format!(" stroke-opacity=\"{alpha}\""),
+        };
+        let (first, last) = match (self.fade_from, self.points.first(), self.points.last()) {
+            (Some(_), Some(first), Some(last)) => (first, last),
+            _ => {
+                return format!(
+                    "<polyline points=\"{points}\" fill=\"none\" stroke=\"{}\" stroke-width=\"1\"{blend} />",
+                    self.color.to_hex_string()
+                )
+            }
+        };
+        let id = format!("fade{index}");
+        format!(
+            "<g><linearGradient id=\"{id}\" gradientUnits=\"userSpaceOnUse\" x1=\"{}\" y1=\"{}\" x2=\"{}\" \
+             y2=\"{}\"><stop offset=\"0\" stop-color=\"{}\" /><stop offset=\"1\" stop-color=\"{}\" />\
+             </linearGradient><polyline points=\"{points}\" fill=\"none\" stroke=\"url(#{id})\" \
+             stroke-width=\"1\"{blend} /></g>",
+            first.x,
+            first.y,
+            last.x,
+            last.y,
+            self.fade_from.unwrap_or(self.color).to_hex_string(),
+            self.color.to_hex_string()
+        )
     }
 
     fn z_index(&self) -> u32 {
@@ -402,6 +1402,42 @@ impl Draw for Line {
     fn set_z_index(&mut self, val: u32) {
         self.z_index = val
     }
+
+    fn set_antialias(&mut self, val: bool) {
+        self.antialias = val
+    }
+}
+
+// ----------------------------------------------------------------
+
+// Part of the offsets whose pixels, counted from start, land inside a framebuffer side of this size
+fn visible_range(offsets: Range<isize>, start: f32, size: u32) -> Range<isize> {
+    offsets.start.max((-start).ceil() as isize)..offsets.end.min((size as f32 - start).ceil() as isize)
+}
+
+// Averages every pixel with the BLOOM_RADIUS pixels on either side along each of count lines of length pixels. step
+// is the distance between neighbouring pixels of a line and line_step the distance between the starts of lines
+fn box_blur(buffer: &mut [Color], length: usize, count: usize, step: usize, line_step: usize) {
+    let window = 2 * BLOOM_RADIUS + 1;
+    let mut line = Vec::with_capacity(length);
+    for i in 0..count {
+        line.clear();
+        line.extend((0..length).map(|j| buffer[i * line_step + j * step]));
+        // Running sum over the window ending at k, pixels past either end count as black
+        let mut sum = [0u32; 3];
+        for k in 0..length + BLOOM_RADIUS {
+            if let Some(x) = line.get(k) {
+                sum = [sum[0] + x.r as u32, sum[1] + x.g as u32, sum[2] + x.b as u32];
+            }
+            if let Some(x) = k.checked_sub(window).map(|k| line[k]) {
+                sum = [sum[0] - x.r as u32, sum[1] - x.g as u32, sum[2] - x.b as u32];
+            }
+            if let Some(j) = k.checked_sub(BLOOM_RADIUS) {
+                let [r, g, b] = sum.map(|x| (x / window as u32) as u8);
+                buffer[i * line_step + j * step] = Color::new(r, g, b);
+            }
+        }
+    }
 }
 
 // ----------------------------------------------------------------
@@ -455,9 +1491,10 @@ impl Rect {
 
 impl Draw for Rect {
     fn draw(&self, frame_buffer: &mut FrameBuffer) {
-        // TODO: Check if on screen
-        for y in 0..(self.size.y as usize) {
-            for x in 0..(self.size.x as usize) {
+        let rows = visible_range(0..self.size.y as isize, self.pos.y, frame_buffer.size().y);
+        let columns = visible_range(0..self.size.x as isize, self.pos.x, frame_buffer.size().x);
+        for y in rows {
+            for x in columns.clone() {
                 frame_buffer.set_pixel(
                     Vector2D::new((x as f32) + self.pos.x, (y as f32) + self.pos.y),
                     self.color,
@@ -498,7 +1535,7 @@ impl Draw for Rect {
     }
 
     fn scale(&self, times: f32) -> Box<dyn Draw> {
-        let new_size = Vector2D::new(self.size.x * times, self.size.x * times);
+        let new_size = Vector2D::new(self.size.x * times, self.size.y * times);
         Box::new(Rect::new(
             Vector2D::new(self.pos.x * times, self.pos.y * times),
             new_size,
@@ -507,6 +1544,17 @@ impl Draw for Rect {
         ))
     }
 
+    fn to_svg_element(&self, _index: usize) -> String {
+        format!(
+            "<rect x=\"{}\" y=\"{}\" width=\"{}\" height=\"{}\" fill=\"{}\" />",
+            self.pos.x,
+            self.pos.y,
+            self.size.x,
+            self.size.y,
+            self.color.to_hex_string()
+        )
+    }
+
     fn z_index(&self) -> u32 {
         self.z_index
     }
@@ -523,6 +1571,10 @@ pub struct Circle {
     pos: Vector2D<f32>,
     radius: f32,
     color: Color,
+    // Rim drawn over the fill, draw_outline uses color if None
+    outline_color: Option<Color>,
+    // Only the rim is drawn if false
+    filled: bool,
     z_index: u32,
 }
 
@@ -535,6 +1587,8 @@ impl Circle {
             radius: radius.abs(),
             z_index,
             color,
+            outline_color: None,
+            filled: true,
         }
     }
 
@@ -551,6 +1605,14 @@ impl Circle {
         &self.color
     }
 
+    pub fn outline_color(&self) -> &Option<Color> {
+        &self.outline_color
+    }
+
+    pub fn filled(&self) -> &bool {
+        &self.filled
+    }
+
     // Setters
     pub fn set_radius(&mut self, val: f32) {
         self.radius = val.abs()
@@ -563,29 +1625,17 @@ impl Circle {
     pub fn color_mut(&mut self, val: Color) {
         self.color = val
     }
-}
 
-impl Draw for Circle {
-    fn draw(&self, frame_buffer: &mut FrameBuffer) {
-        if self.pos.x + self.radius >= 0.0
-            && self.pos.x - self.radius <= frame_buffer.size().x as f32
-            && self.pos.y + self.radius >= 0.0
-            && self.pos.y - self.radius <= frame_buffer.size().y as f32
-        {
-            for y in -(self.radius as isize)..(self.radius as isize) {
-                for x in -(self.radius as isize)..(self.radius as isize) {
-                    if (x.pow(2) + y.pow(2)) <= (self.radius as isize).pow(2) {
-                        frame_buffer.set_pixel(
-                            Vector2D::new(self.pos.x + (x as f32), self.pos.y + (y as f32)),
-                            self.color,
-                        )
-                    }
-                }
-            }
-        }
+    pub fn set_outline_color(&mut self, val: Option<Color>) {
+        self.outline_color = val
     }
 
-    fn draw_outline(&self, frame_buffer: &mut FrameBuffer) {
+    pub fn set_filled(&mut self, val: bool) {
+        self.filled = val
+    }
+
+    // Methods
+    fn draw_rim(&self, frame_buffer: &mut FrameBuffer, color: Color) {
         fn draw_circle(c: Vector2D<i32>, p: Vector2D<i32>, color: Color, frame_buffer: &mut FrameBuffer) {
             frame_buffer.set_pixel(Vector2D::new((c.x + p.x) as f32, (c.y + p.y) as f32), color);
             frame_buffer.set_pixel(Vector2D::new((c.x - p.x) as f32, (c.y + p.y) as f32), color);
@@ -598,13 +1648,13 @@ impl Draw for Circle {
         }
 
         if self.radius < 2.0 {
-            frame_buffer.set_pixel(self.pos, self.color)
+            frame_buffer.set_pixel(self.pos, color)
         } else {
             let center_pos: Vector2D<i32> = Vector2D::new(self.pos.x as i32, self.pos().y as i32);
             let mut p: Vector2D<i32> = Vector2D::new(0, self.radius as i32);
             let mut d: i32 = 3 - 2 * (self.radius as i32);
 
-            draw_circle(center_pos, p, self.color, frame_buffer);
+            draw_circle(center_pos, p, color, frame_buffer);
             while p.y >= p.x {
                 p.x += 1;
                 if d > 0 {
@@ -613,24 +1663,236 @@ impl Draw for Circle {
                 } else {
                     d += 4 * p.x + 6
                 }
-                draw_circle(center_pos, p, self.color, frame_buffer);
+                draw_circle(center_pos, p, color, frame_buffer);
+            }
+        }
+    }
+}
+
+impl Draw for Circle {
+    fn draw(&self, frame_buffer: &mut FrameBuffer) {
+        if !self.filled {
+            return self.draw_outline(frame_buffer);
+        }
+        if self.pos.x + self.radius >= 0.0
+            && self.pos.x - self.radius <= frame_buffer.size().x as f32
+            && self.pos.y + self.radius >= 0.0
+            && self.pos.y - self.radius <= frame_buffer.size().y as f32
+        {
+            let offsets = -(self.radius as isize)..(self.radius as isize);
+            let rows = visible_range(offsets.clone(), self.pos.y, frame_buffer.size().y);
+            let columns = visible_range(offsets, self.pos.x, frame_buffer.size().x);
+            for y in rows {
+                for x in columns.clone() {
+                    if (x.pow(2) + y.pow(2)) <= (self.radius as isize).pow(2) {
+                        frame_buffer.set_pixel(
+                            Vector2D::new(self.pos.x + (x as f32), self.pos.y + (y as f32)),
+                            self.color,
+                        )
+                    }
+                }
+            }
+            if let Some(outline_color) = self.outline_color {
+                self.draw_rim(frame_buffer, outline_color)
             }
         }
     }
 
+    fn draw_outline(&self, frame_buffer: &mut FrameBuffer) {
+        self.draw_rim(frame_buffer, self.outline_color.unwrap_or(self.color))
+    }
+
     fn offset(&self, offset_by: Vector2D<f32>) -> Box<dyn Draw> {
-        Box::new(Circle::new(self.pos + offset_by, self.radius, self.z_index, self.color))
+        let mut circle = self.clone();
+        circle.pos += offset_by;
+        Box::new(circle)
     }
 
     fn scale(&self, times: f32) -> Box<dyn Draw> {
-        Box::new(Circle::new(
-            Vector2D::new(self.pos.x * times, self.pos.y * times),
-            self.radius * times,
+        let mut circle = self.clone();
+        circle.pos = Vector2D::new(self.pos.x * times, self.pos.y * times);
+        circle.radius = self.radius * times;
+        Box::new(circle)
+    }
+
+    fn scale_with_min_size(&self, times: f32, min_size: f32) -> Box<dyn Draw> {
+        let mut circle = self.clone();
+        circle.pos = Vector2D::new(self.pos.x * times, self.pos.y * times);
+        circle.radius = (self.radius * times).max(min_size);
+        Box::new(circle)
+    }
+
+    fn to_svg_element(&self, _index: usize) -> String {
+        let fill = match self.filled {
+            true => self.color.to_hex_string(),
+            false => "none".to_string(),
+        };
+        let stroke = match (self.outline_color, self.filled) {
+            (Some(color), _) => format!(" stroke=\"{}\"", color.to_hex_string()),
+            (None, false) => format!(" stroke=\"{}\"", self.color.to_hex_string()),
+            (None, true) => String::new(),
+        };
+        format!(
+            "<circle cx=\"{}\" cy=\"{}\" r=\"{}\" fill=\"{}\"{} />",
+            self.pos.x, self.pos.y, self.radius, fill, stroke
+        )
+    }
+
+    fn z_index(&self) -> u32 {
+        self.z_index
+    }
+
+    fn set_z_index(&mut self, val: u32) {
+        self.z_index = val
+    }
+}
+
+// ----------------------------------------------------------------
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Triangle {
+    a: Vector2D<f32>,
+    b: Vector2D<f32>,
+    c: Vector2D<f32>,
+    color: Color,
+    z_index: u32,
+}
+
+#[allow(dead_code)]
+impl Triangle {
+    // Constructor
+    pub fn new(a: Vector2D<f32>, b: Vector2D<f32>, c: Vector2D<f32>, z_index: u32, color: Color) -> Triangle {
+        Triangle {
+            a,
+            b,
+            c,
+            color,
+            z_index,
+        }
+    }
+
+    // Tip at tip pointing along direction, the base is width wide and length behind the tip
+    pub fn arrowhead(
+        tip: Vector2D<f32>,
+        direction: Vector2D<f32>,
+        length: f32,
+        width: f32,
+        z_index: u32,
+        color: Color,
+    ) -> Triangle {
+        let along = direction.normalise();
+        let across = Vector2D::new(-along.y, along.x) * (width / 2.0);
+        let base = tip - along * length;
+        Triangle::new(tip, base + across, base - across, z_index, color)
+    }
+
+    // Immutable access
+    pub fn a(&self) -> &Vector2D<f32> {
+        &self.a
+    }
+
+    pub fn b(&self) -> &Vector2D<f32> {
+        &self.b
+    }
+
+    pub fn c(&self) -> &Vector2D<f32> {
+        &self.c
+    }
+
+    pub fn color(&self) -> &Color {
+        &self.color
+    }
+
+    // Setters
+    pub fn set_a(&mut self, val: Vector2D<f32>) {
+        self.a = val
+    }
+
+    pub fn set_b(&mut self, val: Vector2D<f32>) {
+        self.b = val
+    }
+
+    pub fn set_c(&mut self, val: Vector2D<f32>) {
+        self.c = val
+    }
+
+    pub fn set_color(&mut self, val: Color) {
+        self.color = val
+    }
+}
+
+impl Draw for Triangle {
+    // Fills every pixel whose center is inside, one row at a time between the long edge from the top to the bottom
+    // vertex and whichever of the two short edges the row crosses
+    fn draw(&self, frame_buffer: &mut FrameBuffer) {
+        let mut vertices = [self.a, self.b, self.c];
+        vertices.sort_by(|p, q| p.y.total_cmp(&q.y));
+        let [top, middle, bottom] = vertices;
+        let edge_x = |p: Vector2D<f32>, q: Vector2D<f32>, y: f32| {
+            if q.y == p.y {
+                p.x
+            } else {
+                p.x + (q.x - p.x) * (y - p.y) / (q.y - p.y)
+            }
+        };
+
+        let size = *frame_buffer.size();
+        let rows = (top.y - 0.5).ceil().max(0.0) as isize..(bottom.y - 0.5).ceil().min(size.y as f32) as isize;
+        for y in rows {
+            let center = y as f32 + 0.5;
+            let long = edge_x(top, bottom, center);
+            let short = if center < middle.y {
+                edge_x(top, middle, center)
+            } else {
+                edge_x(middle, bottom, center)
+            };
+            let start = (long.min(short) - 0.5).ceil().max(0.0) as isize;
+            let end = (long.max(short) - 0.5).ceil().min(size.x as f32) as isize;
+            for x in start..end {
+                frame_buffer.set_pixel(Vector2D::new(x as f32, y as f32), self.color)
+            }
+        }
+    }
+
+    fn draw_outline(&self, frame_buffer: &mut FrameBuffer) {
+        for (start, end) in [(self.a, self.b), (self.b, self.c), (self.c, self.a)] {
+            frame_buffer.draw(&Line::new(start, end, self.z_index, self.color))
+        }
+    }
+
+    fn offset(&self, offset_by: Vector2D<f32>) -> Box<dyn Draw> {
+        Box::new(Triangle::new(
+            self.a + offset_by,
+            self.b + offset_by,
+            self.c + offset_by,
             self.z_index,
             self.color,
         ))
     }
 
+    fn scale(&self, times: f32) -> Box<dyn Draw> {
+        Box::new(Triangle::new(
+            self.a * times,
+            self.b * times,
+            self.c * times,
+            self.z_index,
+            self.color,
+        ))
+    }
+
+    fn to_svg_element(&self, _index: usize) -> String {
+        format!(
+            "<polygon points=\"{},{} {},{} {},{}\" fill=\"{}\" />",
+            self.a.x,
+            self.a.y,
+            self.b.x,
+            self.b.y,
+            self.c.x,
+            self.c.y,
+            self.color.to_hex_string()
+        )
+    }
+
     fn z_index(&self) -> u32 {
         self.z_index
     }
@@ -0,0 +1,251 @@
+#[cfg(feature = "native")]
+use itertools::Itertools;
+#[cfg(feature = "native")]
+use minifb::{Key, KeyRepeat, Window};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+
+// ----------------------------------------------------------------
+
+// Everything the window loop reacts to from the keyboard
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+pub enum Action {
+    Quit,
+    AddBody,
+    RemoveBody,
+    PrintBody,
+    SelectBody,
+    DeleteSelected,
+    DuplicateSelected,
+    Undo,
+    Redo,
+    ToggleFixed,
+    SpeedUp,
+    SpeedDown,
+    SlowDownTime,
+    SpeedUpTime,
+    LowerRestitution,
+    RaiseRestitution,
+    IncreaseGravConst,
+    DecreaseGravConst,
+    FlipGravity,
+    AdjustMass,
+    ResetContents,
+    LoadFigureEight,
+    LoadOrbits,
+    TogglePredictionCone,
+    ToggleTrailBlendMode,
+    ToggleTrailFade,
+    CycleTrailLength,
+    CycleTrailStyle,
+    ClearTrails,
+    ToggleCenterOfMass,
+    ToggleForceArrows,
+    ToggleField,
+    ToggleMinimap,
+    ToggleBloom,
+    CycleColorMode,
+    MoveUp,
+    MoveDown,
+    MoveRight,
+    MoveLeft,
+    ZoomIn,
+    ZoomOut,
+    ResetView,
+    ZoomToFit,
+    CycleTheme,
+    ToggleAntialias,
+    ToggleGrid,
+    Perturb,
+    FocusClosestPair,
+    SaveScene,
+    LoadScene,
+    TogglePhysics,
+    StepOnce,
+    ExportSvg,
+    ToggleRecording,
+    ToggleGifRecording,
+    ToggleHud,
+    ToggleMouseReadout,
+    // Only does something when built with the egui feature
+    TogglePanel,
+}
+
+impl Action {
+    // These only trigger while a Ctrl key is held and every other action only while none is, so they can share keys
+    pub fn needs_ctrl(&self) -> bool {
+        matches!(self, Action::Undo | Action::Redo)
+    }
+}
+
+// Maps Action names to the names of minifb::Key variants, e.g. "AddBody" to ["Q"] or "SpeedUp" to ["NumPadPlus"]
+pub type KeymapConfig = BTreeMap<String, Vec<String>>;
+
+// ----------------------------------------------------------------
+
+#[cfg(feature = "native")]
+#[derive(Debug, Clone, PartialEq)]
+pub struct Keymap {
+    bindings: BTreeMap<Action, Vec<Key>>,
+}
+
+#[cfg(feature = "native")]
+impl Default for Keymap {
+    fn default() -> Self {
+        let bindings = [
+            (Action::Quit, vec![Key::Escape]),
+            (Action::AddBody, vec![Key::Q]),
+            (Action::RemoveBody, vec![Key::E]),
+            (Action::PrintBody, vec![Key::P]),
+            (Action::SelectBody, vec![Key::V]),
+            (Action::DeleteSelected, vec![Key::Delete]),
+            (Action::DuplicateSelected, vec![Key::Insert]),
+            (Action::Undo, vec![Key::Z]),
+            (Action::Redo, vec![Key::Y]),
+            (Action::ToggleFixed, vec![Key::Z]),
+            (Action::SpeedUp, vec![Key::NumPadPlus]),
+            (Action::SpeedDown, vec![Key::NumPadMinus]),
+            (Action::SlowDownTime, vec![Key::LeftBracket]),
+            (Action::SpeedUpTime, vec![Key::RightBracket]),
+            (Action::LowerRestitution, vec![Key::Minus]),
+            (Action::RaiseRestitution, vec![Key::Equal]),
+            (Action::IncreaseGravConst, vec![Key::PageUp]),
+            (Action::DecreaseGravConst, vec![Key::PageDown]),
+            (Action::FlipGravity, vec![Key::F8]),
+            (Action::AdjustMass, vec![Key::LeftShift, Key::RightShift]),
+            (Action::ResetContents, vec![Key::R]),
+            (Action::LoadFigureEight, vec![Key::Key1]),
+            (Action::LoadOrbits, vec![Key::Key2]),
+            (Action::TogglePredictionCone, vec![Key::C]),
+            (Action::ToggleTrailBlendMode, vec![Key::B]),
+            (Action::ToggleTrailFade, vec![Key::F]),
+            (Action::CycleTrailLength, vec![Key::L]),
+            (Action::CycleTrailStyle, vec![Key::Semicolon]),
+            (Action::ClearTrails, vec![Key::U]),
+            (Action::ToggleCenterOfMass, vec![Key::O]),
+            (Action::ToggleForceArrows, vec![Key::F3]),
+            (Action::ToggleField, vec![Key::F4]),
+            (Action::ToggleMinimap, vec![Key::F10]),
+            (Action::ToggleBloom, vec![Key::F11]),
+            (Action::CycleColorMode, vec![Key::G]),
+            (Action::MoveUp, vec![Key::Up, Key::W]),
+            (Action::MoveDown, vec![Key::Down, Key::S]),
+            (Action::MoveRight, vec![Key::Right, Key::D]),
+            (Action::MoveLeft, vec![Key::Left, Key::A]),
+            (Action::ZoomIn, vec![Key::M]),
+            (Action::ZoomOut, vec![Key::N]),
+            (Action::ResetView, vec![Key::Home]),
+            (Action::ZoomToFit, vec![Key::End]),
+            (Action::CycleTheme, vec![Key::T]),
+            (Action::ToggleAntialias, vec![Key::Y]),
+            (Action::ToggleGrid, vec![Key::F2]),
+            (Action::Perturb, vec![Key::K]),
+            (Action::FocusClosestPair, vec![Key::J]),
+            (Action::SaveScene, vec![Key::F5]),
+            (Action::LoadScene, vec![Key::F9]),
+            (Action::TogglePhysics, vec![Key::Space]),
+            (Action::StepOnce, vec![Key::Period]),
+            (Action::ExportSvg, vec![Key::X]),
+            (Action::ToggleRecording, vec![Key::F6]),
+            (Action::ToggleGifRecording, vec![Key::F7]),
+            (Action::ToggleHud, vec![Key::H]),
+            (Action::ToggleMouseReadout, vec![Key::I]),
+            (Action::TogglePanel, vec![Key::F12]),
+        ];
+        Keymap {
+            bindings: bindings.into_iter().collect(),
+        }
+    }
+}
+
+#[cfg(feature = "native")]
+#[allow(dead_code)]
+impl Keymap {
+    // Constructor
+    // Starts from the default layout, actions listed in the config replace all of their default keys
+    pub fn from_config(config: &KeymapConfig) -> Keymap {
+        let mut keymap = Keymap::default();
+        for (action_name, names) in config {
+            let action: Action = match serde_json::from_str(&format!("{action_name:?}")) {
+                Ok(v) => v,
+                Err(_) => {
+                    eprintln!("Unknown action {action_name:?}");
+                    continue;
+                }
+            };
+            let keys = names
+                .iter()
+                .filter_map(|name| {
+                    let key = key_from_name(name);
+                    if key.is_none() {
+                        eprintln!("Unknown key {name:?} bound to {action:?}");
+                    }
+                    key
+                })
+                .collect();
+            keymap.bindings.insert(action, keys);
+        }
+        for (action_1, action_2, key) in keymap.conflicts() {
+            eprintln!("Key {key:?} is bound to both {action_1:?} and {action_2:?}");
+        }
+        keymap
+    }
+
+    // Immutable access
+    pub fn keys(&self, action: Action) -> &[Key] {
+        self.bindings.get(&action).map(|x| x.as_slice()).unwrap_or(&[])
+    }
+
+    // Setters
+    pub fn set_keys(&mut self, action: Action, keys: Vec<Key>) {
+        self.bindings.insert(action, keys);
+    }
+
+    // Methods
+    // Every key bound to more than one action, as (action, action, key)
+    pub fn conflicts(&self) -> Vec<(Action, Action, Key)> {
+        self.bindings
+            .iter()
+            .flat_map(|(action, keys)| keys.iter().map(move |key| (*action, *key)))
+            .tuple_combinations()
+            .filter(|((action_1, key_1), (action_2, key_2))| {
+                key_1 == key_2 && action_1.needs_ctrl() == action_2.needs_ctrl()
+            })
+            .map(|((action_1, key), (action_2, _))| (action_1, action_2, key))
+            .collect()
+    }
+
+    pub fn is_down(&self, window: &Window, action: Action) -> bool {
+        self.keys(action).iter().any(|x| window.is_key_down(*x))
+    }
+
+    pub fn is_pressed(&self, window: &Window, action: Action, repeat: KeyRepeat) -> bool {
+        let ctrl_down = window.is_key_down(Key::LeftCtrl) || window.is_key_down(Key::RightCtrl);
+        action.needs_ctrl() == ctrl_down && self.keys(action).iter().any(|x| window.is_key_pressed(*x, repeat))
+    }
+}
+
+#[cfg(feature = "native")]
+fn key_from_name(name: &str) -> Option<Key> {
+    KEYS.iter().copied().find(|x| format!("{x:?}") == name)
+}
+
+#[cfg(feature = "native")]
+#[rustfmt::skip]
+const KEYS: &[Key] = &[
+    Key::Key0, Key::Key1, Key::Key2, Key::Key3, Key::Key4, Key::Key5, Key::Key6, Key::Key7, Key::Key8, Key::Key9,
+    Key::A, Key::B, Key::C, Key::D, Key::E, Key::F, Key::G, Key::H, Key::I, Key::J, Key::K, Key::L, Key::M,
+    Key::N, Key::O, Key::P, Key::Q, Key::R, Key::S, Key::T, Key::U, Key::V, Key::W, Key::X, Key::Y, Key::Z,
+    Key::F1, Key::F2, Key::F3, Key::F4, Key::F5, Key::F6, Key::F7, Key::F8, Key::F9, Key::F10, Key::F11, Key::F12,
+    Key::F13, Key::F14, Key::F15,
+    Key::Down, Key::Left, Key::Right, Key::Up,
+    Key::Apostrophe, Key::Backquote, Key::Backslash, Key::Comma, Key::Equal, Key::LeftBracket, Key::Minus,
+    Key::Period, Key::RightBracket, Key::Semicolon, Key::Slash,
+    Key::Backspace, Key::Delete, Key::End, Key::Enter, Key::Escape, Key::Home, Key::Insert, Key::Menu,
+    Key::PageDown, Key::PageUp, Key::Pause, Key::Space, Key::Tab,
+    Key::NumLock, Key::CapsLock, Key::ScrollLock, Key::LeftShift, Key::RightShift, Key::LeftCtrl, Key::RightCtrl,
+    Key::NumPad0, Key::NumPad1, Key::NumPad2, Key::NumPad3, Key::NumPad4, Key::NumPad5, Key::NumPad6,
+    Key::NumPad7, Key::NumPad8, Key::NumPad9,
+    Key::NumPadDot, Key::NumPadSlash, Key::NumPadAsterisk, Key::NumPadMinus, Key::NumPadPlus, Key::NumPadEnter,
+    Key::LeftAlt, Key::RightAlt, Key::LeftSuper, Key::RightSuper,
+];
@@ -0,0 +1,129 @@
+#[cfg(feature = "audio")]
+pub mod audio;
+pub mod gamepad;
+pub mod graphics;
+pub mod keymap;
+#[cfg(feature = "egui")]
+pub mod panel;
+#[cfg(feature = "native")]
+pub mod recorder;
+pub mod settings;
+pub mod simulation;
+#[cfg(target_arch = "wasm32")]
+pub mod web;
+
+use settings::Settings;
+use simulation::*;
+use vector2d::Vector2D;
+
+// Pixels between the cursor and the corner of the tooltip
+const TOOLTIP_OFFSET: f32 = 16.0;
+// Pixels around the screen bodies are still drawn in, for lines thicker than a pixel
+const CULL_MARGIN: f32 = 4.0;
+
+// ----------------------------------------------------------------
+
+pub fn new_scene(settings: &Settings, resolution: Vector2D<u32>) -> graphics::Scene {
+    let mut scene = graphics::Scene::new(vec![], resolution, Some(Vector2D::new(0.1, 5.0)));
+    scene.set_min_body_radius(settings.min_body_radius);
+    scene.set_theme(settings.theme.theme());
+    scene.set_background(settings.background);
+    scene.set_antialias(settings.antialias);
+    scene.set_show_grid(settings.show_grid);
+    scene.set_grid_spacing(settings.grid_spacing);
+    scene.set_show_field(settings.show_field);
+    scene.set_field_density(settings.field_density);
+    scene.set_show_minimap(settings.show_minimap);
+    scene.set_bloom(settings.bloom);
+    scene
+}
+
+// The selected theme with the background replaced if one is set, trails fade towards it
+pub fn theme(settings: &Settings) -> graphics::Theme {
+    let mut theme = settings.theme.theme();
+    if let Some(background) = settings.background {
+        theme.background = background;
+    }
+    theme
+}
+
+// Random bodies with everything else taken from the settings, the bodies are the same every run if a seed is set
+pub fn new_simulation(settings: &Settings) -> Simulation {
+    let mut simulation = Simulation::new(
+        Simulation::random_bodies(settings.num_of_bodies, settings.seed),
+        Some(settings.grav_const),
+        Some(settings.physics_speed),
+        None,
+        settings.collision_mode,
+    );
+    simulation.set_seed(settings.seed);
+    simulation.set_time_scale(settings.time_scale);
+    simulation.set_gravity_mode(settings.gravity_mode);
+    simulation.set_integrator(settings.integrator);
+    simulation.set_restitution(settings.restitution);
+    simulation.set_damping(settings.damping);
+    simulation.set_softening(settings.softening);
+    simulation.set_bounds(settings.bounds);
+    simulation.set_boundary_mode(settings.boundary_mode);
+    simulation.set_max_trail_length(settings.max_trail_length);
+    simulation.set_trail_style(settings.trail_style);
+    simulation.set_prediction_mode(settings.prediction_mode);
+    simulation.set_color_mode(settings.color_mode);
+    simulation.set_collision_visuals(settings.collision_visuals);
+    simulation.set_theme(theme(settings));
+    simulation.set_freeze_dragged_body(settings.freeze_dragged_body);
+    if let Some(path) = &settings.csv_log {
+        match CsvLogger::create(path) {
+            Ok(csv_logger) => simulation.set_csv_logger(Some(csv_logger)),
+            Err(e) => eprintln!("Unable to create {path}: {e}"),
+        }
+    }
+    simulation
+}
+
+// Replaces the scene's contents with the simulation's shapes, sorted for drawing. Bodies off screen are left out
+pub fn update_scene(scene: &mut graphics::Scene, simulation: &Simulation) {
+    let (min, max) = scene.visible_world_rect();
+    // Bodies are drawn at least min_body_radius pixels big however small they are in the world
+    let margin = (scene.min_body_radius() + CULL_MARGIN) / scene.get_scale();
+    let margin = Vector2D::new(margin, margin);
+    *scene.contents_mut() = simulation.shapes_within(from_f32_vec(min - margin), from_f32_vec(max + margin));
+    if *scene.show_field() {
+        let mut field = simulation.field_shapes(&scene.field_sample_points(), scene.field_arrow_length());
+        scene.contents_mut().append(&mut field);
+    }
+    scene.sort_contents();
+}
+
+// Every body as a dot in its own color, does nothing unless the scene's minimap is shown
+pub fn draw_minimap(scene: &graphics::Scene, simulation: &Simulation, frame_buffer: &mut graphics::FrameBuffer) {
+    if *scene.show_minimap() {
+        let points: Vec<_> = simulation
+            .bodies()
+            .iter()
+            .map(|x| (to_f32_vec(*x.pos()), *x.color()))
+            .collect();
+        let extent = simulation.extent().map(|(min, max)| (to_f32_vec(min), to_f32_vec(max)));
+        scene.draw_minimap(frame_buffer, &points, extent);
+    }
+}
+
+// Stats of the body under the cursor and where on screen to show them, next to the cursor
+pub fn body_tooltip(
+    scene: &graphics::Scene,
+    simulation: &Simulation,
+    mouse_world_pos: Vector2D<f32>,
+) -> Option<(Vector2D<f32>, String)> {
+    let min_pick_radius = scene.min_body_radius() / scene.get_scale();
+    let body = simulation
+        .get_body_on_point_index(from_f32_vec(mouse_world_pos), Scalar::from(min_pick_radius))
+        .and_then(|x| simulation.get_body(x))?;
+    let text = format!(
+        "Mass: {:.1}\nSpeed: {:.3}\nRadius: {:.1}",
+        body.mass(),
+        body.velocity().speed(),
+        body.radius()
+    );
+    let pos = scene.world_to_screen_coords(mouse_world_pos) + Vector2D::new(TOOLTIP_OFFSET, TOOLTIP_OFFSET);
+    Some((pos, text))
+}
@@ -1,31 +1,69 @@
-mod graphics;
-mod simulation;
-
+#[cfg(feature = "audio")]
+use audio::CollisionSounds;
+#[cfg(feature = "audio")]
+use frame_buffer::audio;
+#[cfg(feature = "egui")]
+use frame_buffer::panel;
+use frame_buffer::{
+    body_tooltip, draw_minimap, gamepad, graphics, keymap, new_scene, new_simulation, recorder, settings, simulation,
+    theme, update_scene,
+};
+#[cfg(feature = "gamepad")]
+use gamepad::Gamepad;
+#[cfg(not(feature = "gamepad"))]
+use gamepad::GamepadInput;
+use keymap::{Action, Keymap};
+#[cfg(feature = "egui")]
+use panel::{ControlPanel, PanelInput};
+use recorder::{GifRecorder, Recorder};
+use settings::Settings;
 use simulation::*;
 
-use minifb::{Key, KeyRepeat, ScaleMode, Window, WindowOptions};
+use clap::Parser;
+use minifb::{KeyRepeat, ScaleMode, Window, WindowOptions};
+use rand::rngs::StdRng;
+use rand::SeedableRng;
+use std::collections::VecDeque;
+use std::io::Write;
+use std::time::Instant;
 use vector2d::Vector2D;
 
-const WIDTH: usize = 1260;
-const HEIGHT: usize = 720;
-const NUM_OF_BODIES: usize = 10;
-
-// TODO:
-// Console mode
-// Wasm version
-// Cursor insert mode
-// Resizing support
-// Zoom onto mouse
+const SVG_EXPORT_PATH: &str = "scene.svg";
+const SCENE_SAVE_PATH: &str = "scene.json";
+const FPS_WINDOW: usize = 30;
+// Pixels from the middle of the screen a body placed with a gamepad is aimed at with the right stick pushed all the way
+const GAMEPAD_AIM_REACH: f32 = 60.0;
 
 fn main() {
     std::env::set_var("RUST_BACKTRACE", "1");
 
+    // The options only change this run, saved_settings is what gets saved again with the changes made while running
+    let mut saved_settings = Settings::load(settings::SETTINGS_PATH);
+    let mut settings = saved_settings.clone();
+    if let Err(e) = settings.apply_args(settings::Args::parse()) {
+        eprintln!("Unable to load config: {e}");
+        std::process::exit(1);
+    }
+    let initial_settings = settings.clone();
+
+    if let Some(steps) = settings.headless_steps {
+        new_simulation(&settings)
+            .run_headless(steps)
+            .iter()
+            .for_each(|x| println!("{x}"));
+        return;
+    }
+    if settings.console {
+        run_console(&settings);
+    }
+
     let mut window = Window::new(
         "Press ESC to exit",
-        WIDTH,
-        HEIGHT,
+        settings.width,
+        settings.height,
         WindowOptions {
             scale_mode: ScaleMode::Stretch,
+            resize: true,
             ..WindowOptions::default()
         },
     )
@@ -34,77 +72,358 @@ fn main() {
     // Limit to max ~60 fps update rate
     window.limit_update_rate(Some(std::time::Duration::from_micros(16600)));
 
+    let keymap = Keymap::from_config(&settings.keymap);
+    #[cfg(feature = "gamepad")]
+    let mut gamepad = Gamepad::new();
+    #[cfg(feature = "egui")]
+    let mut control_panel = ControlPanel::new();
+    #[cfg(feature = "audio")]
+    let mut collision_sounds = CollisionSounds::new();
     let mut physics_on = true;
+    let mut perturb_rng = StdRng::seed_from_u64(settings.perturb_seed);
+    let mut show_mouse_readout = false;
+    let mut show_hud = true;
+    let mut last_mouse_pos: Option<Vector2D<f32>> = None;
+    let mut frame_times: VecDeque<Instant> = VecDeque::with_capacity(FPS_WINDOW + 1);
 
-    let mut scene = graphics::Scene::new(
-        vec![],
-        Vector2D::new(WIDTH as u32, HEIGHT as u32),
-        Some(Vector2D::new(0.1, 5.0)),
-    );
-    let mut simulation = Simulation::new(
-        (0..NUM_OF_BODIES)
-            .into_iter()
-            .map(|_| PhysicsBody::new_rand())
-            .collect(),
-        None,
-        None,
-        CollisionMode::None,
+    let mut scene = new_scene(&settings, Vector2D::new(settings.width as u32, settings.height as u32));
+    let mut simulation = new_simulation(&settings);
+    // Kept across frames so drawing doesn't allocate
+    let mut frame_buffer = graphics::FrameBuffer::new(*scene.res());
+    let mut pixels: Vec<u32> = Vec::new();
+    let mut recorder = Recorder::new(&settings.record_dir);
+    let mut gif_recorder = GifRecorder::new(
+        &settings.gif_path,
+        settings.gif_max_frames,
+        settings.gif_frame_delay,
+        settings.gif_downscale,
     );
 
-    while window.is_open() && !window.is_key_down(Key::Escape) {
+    while window.is_open() && !keymap.is_down(&window, Action::Quit) {
+        // A minimized window reports a size of 0
+        let (width, height) = window.get_size();
+        if (width, height) != (settings.width, settings.height) && width > 0 && height > 0 {
+            settings.width = width;
+            settings.height = height;
+            scene.resize(Vector2D::new(width as u32, height as u32));
+        }
+
+        // Scrolling over a body while holding the modifier changes its mass instead of zooming
+        let adjust_mass = keymap.is_down(&window, Action::AdjustMass)
+            && window
+                .get_mouse_pos(minifb::MouseMode::Discard)
+                .map(|v| scene.screen_to_world_coords(Vector2D::new(v.0, v.1)))
+                .and_then(|x| {
+                    let min_pick_radius = Scalar::from(scene.min_body_radius() / scene.get_scale());
+                    simulation.get_body_on_point_index(from_f32_vec(x), min_pick_radius)
+                })
+                .is_some();
+        let mouse_pos = window
+            .get_mouse_pos(minifb::MouseMode::Discard)
+            .map(|v| Vector2D::new(v.0, v.1));
+        #[cfg(feature = "gamepad")]
+        let pad = gamepad.as_mut().map(|x| x.poll()).unwrap_or_default();
+        #[cfg(not(feature = "gamepad"))]
+        let pad = GamepadInput::default();
+        // Gamepads add, remove and place bodies in the middle of the screen instead of at the cursor
+        let pad_world_pos = (pad.add_body || pad.remove_body || pad.aim.is_some()).then(|| {
+            let center = Vector2D::new(scene.res().x as f32, scene.res().y as f32) / 2.0;
+            scene.screen_to_world_coords(center + pad.aim.unwrap_or(Vector2D::new(0.0, 0.0)) * GAMEPAD_AIM_REACH)
+        });
+        // The panel goes first so clicks and scrolling over it don't reach the simulation or the view
+        #[cfg(feature = "egui")]
+        let panel_has_mouse = {
+            if keymap.is_pressed(&window, Action::TogglePanel, KeyRepeat::No) {
+                control_panel.set_open(!*control_panel.open());
+            }
+            let input = PanelInput {
+                mouse_screen_pos: mouse_pos,
+                mouse_left_down: window.get_mouse_down(minifb::MouseButton::Left),
+                mouse_scroll_wheel: window.get_scroll_wheel().map(|v| v.1),
+            };
+            control_panel.run(*scene.res(), input, &mut simulation)
+        };
+        #[cfg(not(feature = "egui"))]
+        let panel_has_mouse = false;
         simulation.handle_user_input(SimulationInput {
-            add_body: window.is_key_pressed(Key::Q, KeyRepeat::Yes),
-            remove_body: window.is_key_pressed(Key::E, KeyRepeat::Yes),
-            print_body: window.is_key_pressed(Key::R, KeyRepeat::Yes),
-            selected_body: window.is_key_pressed(Key::V, KeyRepeat::No),
-            up_speed: window.is_key_pressed(Key::NumPadPlus, KeyRepeat::Yes),
-            down_speed: window.is_key_pressed(Key::NumPadMinus, KeyRepeat::Yes),
-            reset_contents: window.is_key_pressed(Key::R, KeyRepeat::No),
-            mouse_world_pos: if let Some(v) = window.get_mouse_pos(minifb::MouseMode::Discard) {
-                Some(scene.screen_to_world_coords(Vector2D::new(v.0, v.1)))
-            } else {
-                None
-            },
-            mouse_scroll_wheel: if let Some(v) = window.get_scroll_wheel() {
-                Some(v.1)
-            } else {
-                None
-            },
+            add_body: keymap.is_pressed(&window, Action::AddBody, KeyRepeat::Yes) || pad.add_body,
+            remove_body: keymap.is_pressed(&window, Action::RemoveBody, KeyRepeat::Yes) || pad.remove_body,
+            print_body: keymap.is_pressed(&window, Action::PrintBody, KeyRepeat::Yes),
+            selected_body: keymap.is_pressed(&window, Action::SelectBody, KeyRepeat::No),
+            delete_selected: keymap.is_pressed(&window, Action::DeleteSelected, KeyRepeat::No),
+            duplicate_selected: keymap.is_pressed(&window, Action::DuplicateSelected, KeyRepeat::No),
+            undo: keymap.is_pressed(&window, Action::Undo, KeyRepeat::Yes),
+            redo: keymap.is_pressed(&window, Action::Redo, KeyRepeat::Yes),
+            up_speed: keymap.is_pressed(&window, Action::SpeedUp, KeyRepeat::Yes),
+            down_speed: keymap.is_pressed(&window, Action::SpeedDown, KeyRepeat::Yes),
+            halve_time_scale: keymap.is_pressed(&window, Action::SlowDownTime, KeyRepeat::No),
+            double_time_scale: keymap.is_pressed(&window, Action::SpeedUpTime, KeyRepeat::No),
+            lower_restitution: keymap.is_pressed(&window, Action::LowerRestitution, KeyRepeat::Yes),
+            raise_restitution: keymap.is_pressed(&window, Action::RaiseRestitution, KeyRepeat::Yes),
+            increase_grav_const: keymap.is_pressed(&window, Action::IncreaseGravConst, KeyRepeat::Yes),
+            decrease_grav_const: keymap.is_pressed(&window, Action::DecreaseGravConst, KeyRepeat::Yes),
+            flip_gravity: keymap.is_pressed(&window, Action::FlipGravity, KeyRepeat::No),
+            reset_contents: keymap.is_pressed(&window, Action::ResetContents, KeyRepeat::No),
+            toggle_prediction_cone: keymap.is_pressed(&window, Action::TogglePredictionCone, KeyRepeat::No),
+            toggle_trail_blend_mode: keymap.is_pressed(&window, Action::ToggleTrailBlendMode, KeyRepeat::No),
+            toggle_trail_fade: keymap.is_pressed(&window, Action::ToggleTrailFade, KeyRepeat::No),
+            cycle_trail_length: keymap.is_pressed(&window, Action::CycleTrailLength, KeyRepeat::No),
+            cycle_trail_style: keymap.is_pressed(&window, Action::CycleTrailStyle, KeyRepeat::No),
+            clear_trails: keymap.is_pressed(&window, Action::ClearTrails, KeyRepeat::No),
+            load_figure_eight: keymap.is_pressed(&window, Action::LoadFigureEight, KeyRepeat::No),
+            load_orbits: keymap.is_pressed(&window, Action::LoadOrbits, KeyRepeat::No),
+            toggle_fixed: keymap.is_pressed(&window, Action::ToggleFixed, KeyRepeat::No),
+            toggle_center_of_mass: keymap.is_pressed(&window, Action::ToggleCenterOfMass, KeyRepeat::No),
+            toggle_force_arrows: keymap.is_pressed(&window, Action::ToggleForceArrows, KeyRepeat::No),
+            cycle_color_mode: keymap.is_pressed(&window, Action::CycleColorMode, KeyRepeat::No),
+            mouse_left_down: (window.get_mouse_down(minifb::MouseButton::Left) && !panel_has_mouse)
+                || pad.aim.is_some(),
+            mouse_world_pos: pad_world_pos.or(mouse_pos.map(|x| scene.screen_to_world_coords(x))),
+            mouse_scroll_wheel: window.get_scroll_wheel().filter(|_| !panel_has_mouse).map(|v| v.1),
+            mouse_world_delta: mouse_pos
+                .zip(last_mouse_pos)
+                .map(|(pos, last)| (pos - last) / scene.get_scale()),
+            adjust_mass,
+            min_pick_radius: scene.min_body_radius() / scene.get_scale(),
         });
+        last_mouse_pos = mouse_pos;
         scene.handle_user_input(graphics::SceneUserInput {
-            move_up: window.is_key_down(Key::Up) || window.is_key_down(Key::W),
-            move_down: window.is_key_down(Key::Down) || window.is_key_down(Key::S),
-            move_right: window.is_key_down(Key::Right) || window.is_key_down(Key::D),
-            move_left: window.is_key_down(Key::Left) || window.is_key_down(Key::A),
-            zoom_in: window.is_key_down(Key::M),
-            zoom_out: window.is_key_down(Key::N),
-            reset_view: window.is_key_pressed(Key::R, KeyRepeat::No),
-            mouse_screen_pos: if let Some(v) = window.get_mouse_pos(minifb::MouseMode::Discard) {
-                Some(scene.screen_to_world_coords(Vector2D::new(v.0, v.1)))
-            } else {
-                None
-            },
-            mouse_scroll_wheel: if let Some(v) = window.get_scroll_wheel() {
-                Some(v.1)
-            } else {
-                None
+            move_up: keymap.is_down(&window, Action::MoveUp) || pad.move_up,
+            move_down: keymap.is_down(&window, Action::MoveDown) || pad.move_down,
+            move_right: keymap.is_down(&window, Action::MoveRight) || pad.move_right,
+            move_left: keymap.is_down(&window, Action::MoveLeft) || pad.move_left,
+            zoom_in: keymap.is_down(&window, Action::ZoomIn) || pad.zoom_in,
+            zoom_out: keymap.is_down(&window, Action::ZoomOut) || pad.zoom_out,
+            reset_view: keymap.is_pressed(&window, Action::ResetView, KeyRepeat::No),
+            mouse_pan: window.get_mouse_down(minifb::MouseButton::Middle),
+            mouse_screen_pos: window
+                .get_mouse_pos(minifb::MouseMode::Discard)
+                .map(|v| Vector2D::new(v.0, v.1)),
+            mouse_scroll_wheel: match window.get_scroll_wheel() {
+                Some(v) if !adjust_mass && !panel_has_mouse => Some(v.1),
+                _ => None,
             },
         });
+        if keymap.is_pressed(&window, Action::ZoomToFit, KeyRepeat::No) {
+            if let Some((min, max)) = simulation.extent() {
+                scene.fit_bounds(to_f32_vec(min), to_f32_vec(max))
+            }
+        }
+        if keymap.is_pressed(&window, Action::CycleTheme, KeyRepeat::No) {
+            settings.theme = settings.theme.next();
+            scene.set_theme(settings.theme.theme());
+            simulation.set_theme(theme(&settings));
+        }
+        if keymap.is_pressed(&window, Action::ToggleAntialias, KeyRepeat::No) {
+            settings.antialias = !settings.antialias;
+            scene.set_antialias(settings.antialias);
+        }
+        if keymap.is_pressed(&window, Action::ToggleGrid, KeyRepeat::No) {
+            settings.show_grid = !settings.show_grid;
+            scene.set_show_grid(settings.show_grid);
+        }
+        if keymap.is_pressed(&window, Action::ToggleField, KeyRepeat::No) {
+            settings.show_field = !settings.show_field;
+            scene.set_show_field(settings.show_field);
+        }
+        if keymap.is_pressed(&window, Action::ToggleMinimap, KeyRepeat::No) {
+            settings.show_minimap = !settings.show_minimap;
+            scene.set_show_minimap(settings.show_minimap);
+        }
+        if keymap.is_pressed(&window, Action::ToggleBloom, KeyRepeat::No) {
+            settings.bloom = !settings.bloom;
+            scene.set_bloom(settings.bloom);
+        }
+        if keymap.is_pressed(&window, Action::Perturb, KeyRepeat::No) {
+            simulation.perturb(settings.perturb_magnitude, &mut perturb_rng)
+        }
+        if keymap.is_pressed(&window, Action::FocusClosestPair, KeyRepeat::No) {
+            if let Some((i, j, _)) = simulation.closest_pair() {
+                let (pos_1, pos_2) = (*simulation.bodies()[i].pos(), *simulation.bodies()[j].pos());
+                scene.focus_on(to_f32_vec((pos_1 + pos_2) / 2.0))
+            }
+        }
+        if keymap.is_pressed(&window, Action::SaveScene, KeyRepeat::No) {
+            match simulation.save_to_path(SCENE_SAVE_PATH, false) {
+                Ok(_) => println!("Saved scene to {SCENE_SAVE_PATH}"),
+                Err(e) => eprintln!("Unable to save scene: {e}"),
+            }
+        }
+        if keymap.is_pressed(&window, Action::LoadScene, KeyRepeat::No) {
+            match SimulationState::load(SCENE_SAVE_PATH) {
+                Ok(state) => simulation.set_state(state),
+                Err(e) => eprintln!("Unable to load scene: {e}"),
+            }
+        }
         if let Some(selected_body) = *simulation.selected_body() {
             if let Some(body) = simulation.get_body(selected_body) {
-                scene.focus_on(*body.pos())
+                scene.focus_on_smooth(to_f32_vec(*body.pos()), settings.camera_smoothing)
             }
         }
 
-        physics_on = physics_on ^ window.is_key_pressed(Key::Space, KeyRepeat::No);
-        if physics_on {
-            simulation.physics_tick();
+        physics_on = physics_on ^ keymap.is_pressed(&window, Action::TogglePhysics, KeyRepeat::No);
+        // Advances a single tick per press while paused
+        let step_once = keymap.is_pressed(&window, Action::StepOnce, KeyRepeat::No);
+        if physics_on || step_once {
+            #[cfg_attr(not(feature = "audio"), allow(unused_variables))]
+            let collisions = simulation.physics_tick();
+            #[cfg(feature = "audio")]
+            if let Some(x) = collision_sounds.as_mut() {
+                let width = scene.res().x as f32;
+                x.play(&collisions, |pos| {
+                    scene.world_to_screen_coords(to_f32_vec(pos)).x / width
+                });
+            }
+        }
+
+        update_scene(&mut scene, &simulation);
+        scene.render_into(&mut frame_buffer);
+
+        if keymap.is_pressed(&window, Action::ToggleRecording, KeyRepeat::No) {
+            match recorder.toggle() {
+                Ok(_) if *recorder.recording() => println!("Recording to {:?}", recorder.directory()),
+                Ok(_) => println!("Stopped recording after frame {}", recorder.frame()),
+                Err(e) => eprintln!("Unable to start recording: {e}"),
+            }
+        }
+        if keymap.is_pressed(&window, Action::ToggleGifRecording, KeyRepeat::No) {
+            match gif_recorder.toggle() {
+                Ok(_) if *gif_recorder.recording() => println!("Recording GIF"),
+                Ok(_) => println!("Saved GIF to {:?}", gif_recorder.path()),
+                Err(e) => eprintln!("Unable to save GIF: {e}"),
+            }
+        }
+        // Recorded before the HUD is drawn on top
+        if let Err(e) = recorder.record(&frame_buffer) {
+            eprintln!("Unable to record frame: {e}");
+            recorder.stop();
+        }
+        gif_recorder.record(&frame_buffer);
+
+        if keymap.is_pressed(&window, Action::ExportSvg, KeyRepeat::No) {
+            match std::fs::write(SVG_EXPORT_PATH, scene.to_svg()) {
+                Ok(_) => println!("Exported scene to {SVG_EXPORT_PATH}"),
+                Err(e) => eprintln!("Unable to export scene: {e}"),
+            }
+        }
+
+        draw_minimap(&scene, &simulation, &mut frame_buffer);
+
+        // FPS is averaged over the last FPS_WINDOW frames
+        frame_times.push_back(Instant::now());
+        if frame_times.len() > FPS_WINDOW {
+            frame_times.pop_front();
+        }
+        show_hud ^= keymap.is_pressed(&window, Action::ToggleHud, KeyRepeat::No);
+        if show_hud {
+            let elapsed = frame_times
+                .back()
+                .unwrap()
+                .duration_since(*frame_times.front().unwrap());
+            let fps = if elapsed.as_secs_f32() > 0.0 {
+                (frame_times.len() - 1) as f32 / elapsed.as_secs_f32()
+            } else {
+                0.0
+            };
+            let text = format!(
+                "Bodies: {}\nSpeed: {} x{}\nG: {:.4}\nRestitution: {:.1}\nFPS: {:.0}",
+                simulation.bodies().len(),
+                simulation.physics_speed(),
+                simulation.time_scale(),
+                simulation.grav_const(),
+                simulation.restitution(),
+                fps
+            );
+            frame_buffer.draw_text(Vector2D::new(8.0, 8.0), &text, scene.theme().hud);
+        }
+
+        show_mouse_readout ^= keymap.is_pressed(&window, Action::ToggleMouseReadout, KeyRepeat::No);
+        if show_mouse_readout {
+            if let Some(v) = window.get_mouse_pos(minifb::MouseMode::Discard) {
+                let mouse_world_pos = scene.screen_to_world_coords(Vector2D::new(v.0, v.1));
+                let text = format!(
+                    "X: {:.1} Y: {:.1}  Zoom: {:.3} units/px",
+                    mouse_world_pos.x,
+                    mouse_world_pos.y,
+                    1.0 / scene.get_scale()
+                );
+                frame_buffer.draw_text(
+                    Vector2D::new(
+                        8.0,
+                        (settings.height as u32).saturating_sub(graphics::FrameBuffer::text_size(&text).y + 4) as f32,
+                    ),
+                    &text,
+                    scene.theme().hud,
+                );
+            }
         }
 
-        *scene.contents_mut() = simulation.shapes();
-        scene.sort_contents();
+        #[cfg(feature = "egui")]
+        control_panel.draw(&mut frame_buffer);
+
+        // Drawn last so nothing covers it
+        if let Some(v) = window
+            .get_mouse_pos(minifb::MouseMode::Discard)
+            .filter(|_| !panel_has_mouse)
+        {
+            let mouse_world_pos = scene.screen_to_world_coords(Vector2D::new(v.0, v.1));
+            if let Some((pos, text)) = body_tooltip(&scene, &simulation, mouse_world_pos) {
+                frame_buffer.draw_text_panel(pos, &text, scene.theme().hud, *scene.background());
+            }
+        }
+
+        frame_buffer.write_u32(&mut pixels);
         window
-            .update_with_buffer(&scene.to_frame_buffer().to_vec_u32(), WIDTH, HEIGHT)
+            .update_with_buffer(&pixels, settings.width, settings.height)
             .unwrap();
     }
+
+    // Closing the window while recording still saves the GIF
+    if *gif_recorder.recording() {
+        match gif_recorder.stop() {
+            Ok(_) => println!("Saved GIF to {:?}", gif_recorder.path()),
+            Err(e) => eprintln!("Unable to save GIF: {e}"),
+        }
+    }
+
+    settings.grav_const = *simulation.grav_const();
+    settings.physics_speed = *simulation.physics_speed();
+    settings.time_scale = *simulation.time_scale();
+    settings.collision_mode = *simulation.collision_mode();
+    settings.restitution = *simulation.restitution();
+    settings.gravity_mode = *simulation.gravity_mode();
+    settings.integrator = *simulation.integrator();
+    settings.softening = *simulation.softening();
+    settings.bounds = *simulation.bounds();
+    settings.boundary_mode = *simulation.boundary_mode();
+    settings.max_trail_length = *simulation.max_trail_length();
+    settings.trail_style = *simulation.trail_style();
+    settings.prediction_mode = *simulation.prediction_mode();
+    settings.color_mode = *simulation.color_mode();
+    saved_settings.apply_changes(&initial_settings, &settings);
+    if let Err(e) = saved_settings.save(settings::SETTINGS_PATH) {
+        eprintln!("Unable to save settings: {e}");
+    }
+}
+
+// Redraws the scene in the terminal every frame until the process is interrupted
+fn run_console(settings: &Settings) -> ! {
+    let mut scene = new_scene(settings, Vector2D::new(settings.width as u32, settings.height as u32));
+    let mut simulation = new_simulation(settings);
+
+    print!("\x1b[2J");
+    loop {
+        simulation.physics_tick();
+        update_scene(&mut scene, &simulation);
+
+        // One row is left free so the output doesn't scroll
+        let (columns, rows) = match terminal_size::terminal_size() {
+            Some((terminal_size::Width(w), terminal_size::Height(h))) => (w as u32, h.saturating_sub(1) as u32),
+            None => (80, 23),
+        };
+        print!("\x1b[H{}", scene.to_frame_buffer().to_ansi_string(columns, rows));
+        std::io::stdout().flush().unwrap();
+
+        std::thread::sleep(std::time::Duration::from_micros(16600));
+    }
 }
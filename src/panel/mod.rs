@@ -0,0 +1,295 @@
+// A control panel drawn with egui. Rather than moving the window to a GPU backend, egui's tessellated meshes are
+// rasterized in software into the same FrameBuffer the scene is drawn into, so minifb, the recorder and the HUD all
+// stay as they are. The window loop feeds the mouse in through run and draws the result with draw
+use super::graphics::{Color, FrameBuffer};
+use super::simulation::*;
+
+use egui::epaint::{ClippedPrimitive, ImageData, Primitive, TextureId};
+use egui::{Color32, Context, Event, Modifiers, PointerButton, Pos2, RawInput, Rect, Vec2};
+use std::collections::HashMap;
+use std::time::Instant;
+use vector2d::Vector2D;
+
+// Points egui scrolls per unit minifb reports for the wheel
+const SCROLL_SPEED: f32 = 10.0;
+
+const COLLISION_MODES: [CollisionMode; 5] = [
+    CollisionMode::None,
+    CollisionMode::Collide,
+    CollisionMode::Absorb,
+    CollisionMode::Delete,
+    CollisionMode::Shatter,
+];
+const INTEGRATORS: [Integrator; 2] = [Integrator::Euler, Integrator::VelocityVerlet];
+
+// ----------------------------------------------------------------
+
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct PanelInput {
+    pub mouse_screen_pos: Option<Vector2D<f32>>,
+    pub mouse_left_down: bool,
+    pub mouse_scroll_wheel: Option<f32>,
+}
+
+// Premultiplied RGBA, the way egui hands them over
+struct Texture {
+    size: [usize; 2],
+    pixels: Vec<Color32>,
+}
+
+// ----------------------------------------------------------------
+
+pub struct ControlPanel {
+    context: Context,
+    start: Instant,
+    open: bool,
+    textures: HashMap<TextureId, Texture>,
+    // Freed by egui last frame, only dropped once that frame has been drawn
+    to_free: Vec<TextureId>,
+    primitives: Vec<ClippedPrimitive>,
+    mouse_left_down: bool,
+}
+
+impl Default for ControlPanel {
+    fn default() -> Self {
+        ControlPanel::new()
+    }
+}
+
+#[allow(dead_code)]
+impl ControlPanel {
+    // Constructor
+    pub fn new() -> ControlPanel {
+        ControlPanel {
+            context: Context::default(),
+            start: Instant::now(),
+            open: true,
+            textures: HashMap::new(),
+            to_free: vec![],
+            primitives: vec![],
+            mouse_left_down: false,
+        }
+    }
+
+    // Immutable access
+    pub fn open(&self) -> &bool {
+        &self.open
+    }
+
+    // Setters
+    pub fn set_open(&mut self, val: bool) {
+        self.open = val
+    }
+
+    // Methods
+    // Lays the panel out for this frame, changes made in it are applied to the simulation straight away. Returns
+    // whether the panel has the mouse, the simulation and scene should ignore clicks and scrolling then
+    pub fn run(&mut self, res: Vector2D<u32>, input: PanelInput, simulation: &mut Simulation) -> bool {
+        for id in self.to_free.drain(..) {
+            self.textures.remove(&id);
+        }
+        let pressed_changed = input.mouse_left_down != self.mouse_left_down;
+        self.mouse_left_down = input.mouse_left_down;
+        if !self.open {
+            self.primitives.clear();
+            return false;
+        }
+
+        let mut events = vec![];
+        match input.mouse_screen_pos {
+            Some(pos) => {
+                let pos = Pos2::new(pos.x, pos.y);
+                events.push(Event::PointerMoved(pos));
+                if pressed_changed {
+                    events.push(Event::PointerButton {
+                        pos,
+                        button: PointerButton::Primary,
+                        pressed: input.mouse_left_down,
+                        modifiers: Modifiers::default(),
+                    });
+                }
+            }
+            None => events.push(Event::PointerGone),
+        }
+        if let Some(v) = input.mouse_scroll_wheel {
+            events.push(Event::Scroll(Vec2::new(0.0, v * SCROLL_SPEED)));
+        }
+        let raw_input = RawInput {
+            screen_rect: Some(Rect::from_min_size(Pos2::ZERO, Vec2::new(res.x as f32, res.y as f32))),
+            time: Some(self.start.elapsed().as_secs_f64()),
+            events,
+            ..Default::default()
+        };
+
+        let output = self.context.run(raw_input, |context| {
+            egui::Window::new("Simulation")
+                .resizable(false)
+                .show(context, |ui| controls(ui, simulation));
+        });
+        for (id, delta) in output.textures_delta.set {
+            let (size, pixels): ([usize; 2], Vec<Color32>) = match &delta.image {
+                ImageData::Color(image) => (image.size, image.pixels.clone()),
+                ImageData::Font(image) => (image.size, image.srgba_pixels(None).collect()),
+            };
+            match (delta.pos, self.textures.get_mut(&id)) {
+                // Only part of an existing texture changed
+                (Some([x, y]), Some(texture)) => {
+                    for (row, line) in pixels.chunks_exact(size[0]).enumerate() {
+                        let start = (y + row) * texture.size[0] + x;
+                        texture.pixels[start..start + size[0]].copy_from_slice(line);
+                    }
+                }
+                _ => {
+                    self.textures.insert(id, Texture { size, pixels });
+                }
+            }
+        }
+        self.to_free = output.textures_delta.free;
+        self.primitives = self.context.tessellate(output.shapes, output.pixels_per_point);
+
+        self.context.wants_pointer_input() || self.context.is_pointer_over_area()
+    }
+
+    // Draws what the last run laid out, nothing while the panel is closed
+    pub fn draw(&self, frame_buffer: &mut FrameBuffer) {
+        let size = *frame_buffer.size();
+        let screen = Rect::from_min_size(Pos2::ZERO, Vec2::new(size.x as f32, size.y as f32));
+        for ClippedPrimitive { clip_rect, primitive } in &self.primitives {
+            let mesh = match primitive {
+                Primitive::Mesh(mesh) => mesh,
+                Primitive::Callback(_) => continue,
+            };
+            let texture = match self.textures.get(&mesh.texture_id) {
+                Some(v) => v,
+                None => continue,
+            };
+            let clip_rect = clip_rect.intersect(screen);
+            for triangle in mesh.indices.chunks_exact(3) {
+                let [a, b, c] = [0, 1, 2].map(|i| &mesh.vertices[triangle[i] as usize]);
+                let area = edge(a.pos, b.pos, c.pos);
+                if area == 0.0 {
+                    continue;
+                }
+                let min = a.pos.min(b.pos).min(c.pos).max(clip_rect.min);
+                let max = a.pos.max(b.pos).max(c.pos).min(clip_rect.max);
+                for y in (min.y.floor() as i32)..(max.y.ceil() as i32) {
+                    for x in (min.x.floor() as i32)..(max.x.ceil() as i32) {
+                        // Sampled at the pixel center, dividing by the signed area makes the winding not matter
+                        let p = Pos2::new(x as f32 + 0.5, y as f32 + 0.5);
+                        let weight_a = edge(b.pos, c.pos, p) / area;
+                        let weight_b = edge(c.pos, a.pos, p) / area;
+                        let weight_c = 1.0 - weight_a - weight_b;
+                        if weight_a < 0.0 || weight_b < 0.0 || weight_c < 0.0 {
+                            continue;
+                        }
+                        let uv = a.uv.to_vec2() * weight_a + b.uv.to_vec2() * weight_b + c.uv.to_vec2() * weight_c;
+                        let texel = sample(texture, uv);
+                        let channel = |i: usize| {
+                            let vertex = a.color[i] as f32 * weight_a
+                                + b.color[i] as f32 * weight_b
+                                + c.color[i] as f32 * weight_c;
+                            vertex * texel[i] as f32 / 255.0
+                        };
+                        let alpha = channel(3);
+                        if alpha < 1.0 {
+                            continue;
+                        }
+                        // FrameBuffer blends straight colors, so the premultiplication is undone first
+                        let unpremultiply = |i: usize| (channel(i) * 255.0 / alpha).clamp(0.0, 255.0) as u8;
+                        frame_buffer.blend_pixel(
+                            Vector2D::new(x as f32, y as f32),
+                            Color::new(unpremultiply(0), unpremultiply(1), unpremultiply(2)),
+                            alpha / 255.0,
+                        );
+                    }
+                }
+            }
+        }
+    }
+}
+
+fn controls(ui: &mut egui::Ui, simulation: &mut Simulation) {
+    let mut grav_const = *simulation.grav_const();
+    // The keyboard can still take these past the ends of the sliders
+    if ui
+        .add(
+            egui::Slider::new(&mut grav_const, -1.0..=1.0)
+                .clamp_to_range(false)
+                .text("Gravity"),
+        )
+        .changed()
+    {
+        simulation.set_grav_const(grav_const);
+    }
+    let mut physics_speed = *simulation.physics_speed();
+    if ui
+        .add(egui::Slider::new(&mut physics_speed, 0..=16).text("Ticks per frame"))
+        .changed()
+    {
+        simulation.set_physics_speed(physics_speed);
+    }
+    let mut softening = *simulation.softening();
+    if ui
+        .add(
+            egui::Slider::new(&mut softening, 0.0..=10.0)
+                .clamp_to_range(false)
+                .text("Softening"),
+        )
+        .changed()
+    {
+        simulation.set_softening(softening);
+    }
+    let mut restitution = *simulation.restitution();
+    if ui
+        .add(egui::Slider::new(&mut restitution, 0.0..=1.0).text("Restitution"))
+        .changed()
+    {
+        simulation.set_restitution(restitution);
+    }
+
+    let mut collision_mode = *simulation.collision_mode();
+    egui::ComboBox::from_label("Collisions")
+        .selected_text(format!("{collision_mode:?}"))
+        .show_ui(ui, |ui| {
+            for x in COLLISION_MODES {
+                ui.selectable_value(&mut collision_mode, x, format!("{x:?}"));
+            }
+        });
+    if collision_mode != *simulation.collision_mode() {
+        simulation.set_collision_mode(collision_mode);
+    }
+    let mut integrator = *simulation.integrator();
+    egui::ComboBox::from_label("Integrator")
+        .selected_text(format!("{integrator:?}"))
+        .show_ui(ui, |ui| {
+            for x in INTEGRATORS {
+                ui.selectable_value(&mut integrator, x, format!("{x:?}"));
+            }
+        });
+    if integrator != *simulation.integrator() {
+        simulation.set_integrator(integrator);
+    }
+
+    // Handled the same way as the keys for them
+    let mut input = SimulationInput::default();
+    ui.horizontal(|ui| {
+        input.load_figure_eight = ui.button("Figure eight").clicked();
+        input.load_orbits = ui.button("Orbits").clicked();
+        input.reset_contents = ui.button("Reset").clicked();
+    });
+    if input.load_figure_eight || input.load_orbits || input.reset_contents {
+        simulation.handle_user_input(input);
+    }
+}
+
+// Twice the signed area of the triangle a, b, p
+fn edge(a: Pos2, b: Pos2, p: Pos2) -> f32 {
+    (b.x - a.x) * (p.y - a.y) - (b.y - a.y) * (p.x - a.x)
+}
+
+// Nearest texel to uv, which runs from 0.0 to 1.0 across the texture
+fn sample(texture: &Texture, uv: Vec2) -> Color32 {
+    let x = ((uv.x * texture.size[0] as f32) as usize).min(texture.size[0] - 1);
+    let y = ((uv.y * texture.size[1] as f32) as usize).min(texture.size[1] - 1);
+    texture.pixels[y * texture.size[0] + x]
+}
@@ -0,0 +1,245 @@
+use super::graphics::{Color, FrameBuffer};
+
+use itertools::Itertools;
+use std::borrow::Cow;
+use std::cmp::Reverse;
+use std::collections::HashMap;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use vector2d::Vector2D;
+
+// ----------------------------------------------------------------
+
+// Writes rendered frames as a numbered PNG sequence, e.g. `ffmpeg -i frames/frame_%05d.png out.mp4`
+#[derive(Debug, Clone, PartialEq)]
+pub struct Recorder {
+    directory: PathBuf,
+    recording: bool,
+    // Keeps counting across recordings so a new one doesn't overwrite the last
+    frame: u32,
+}
+
+#[allow(dead_code)]
+impl Recorder {
+    // Constructor
+    pub fn new(directory: impl Into<PathBuf>) -> Recorder {
+        Recorder {
+            directory: directory.into(),
+            recording: false,
+            frame: 0,
+        }
+    }
+
+    // Immutable access
+    pub fn directory(&self) -> &PathBuf {
+        &self.directory
+    }
+
+    pub fn recording(&self) -> &bool {
+        &self.recording
+    }
+
+    pub fn frame(&self) -> &u32 {
+        &self.frame
+    }
+
+    // Methods
+    pub fn start(&mut self) -> io::Result<()> {
+        fs::create_dir_all(&self.directory)?;
+        self.recording = true;
+        Ok(())
+    }
+
+    pub fn stop(&mut self) {
+        self.recording = false
+    }
+
+    pub fn toggle(&mut self) -> io::Result<()> {
+        if self.recording {
+            self.stop();
+            Ok(())
+        } else {
+            self.start()
+        }
+    }
+
+    // Does nothing unless recording
+    pub fn record(&mut self, frame_buffer: &FrameBuffer) -> io::Result<()> {
+        if !self.recording {
+            return Ok(());
+        }
+        self.frame += 1;
+        frame_buffer.save_png(self.directory.join(format!("frame_{:05}.png", self.frame)))
+    }
+}
+
+// ----------------------------------------------------------------
+
+// Keeps rendered frames in memory and writes them all as one looping animated GIF when stopped
+#[derive(Debug, Clone)]
+pub struct GifRecorder {
+    path: PathBuf,
+    recording: bool,
+    frames: Vec<FrameBuffer>,
+    // Frames past this are dropped so a forgotten recording can't eat all the memory
+    max_frames: usize,
+    // In hundredths of a second, as stored in the GIF
+    frame_delay: u16,
+    // Every frame is shrunk by this factor before being kept
+    downscale: u32,
+}
+
+#[allow(dead_code)]
+impl GifRecorder {
+    // Constructor
+    pub fn new(path: impl Into<PathBuf>, max_frames: usize, frame_delay: u16, downscale: u32) -> GifRecorder {
+        GifRecorder {
+            path: path.into(),
+            recording: false,
+            frames: vec![],
+            max_frames,
+            frame_delay,
+            downscale: downscale.max(1),
+        }
+    }
+
+    // Immutable access
+    pub fn path(&self) -> &PathBuf {
+        &self.path
+    }
+
+    pub fn recording(&self) -> &bool {
+        &self.recording
+    }
+
+    pub fn frames(&self) -> &Vec<FrameBuffer> {
+        &self.frames
+    }
+
+    pub fn max_frames(&self) -> &usize {
+        &self.max_frames
+    }
+
+    pub fn frame_delay(&self) -> &u16 {
+        &self.frame_delay
+    }
+
+    pub fn downscale(&self) -> &u32 {
+        &self.downscale
+    }
+
+    // Setters
+    pub fn set_max_frames(&mut self, val: usize) {
+        self.max_frames = val
+    }
+
+    pub fn set_frame_delay(&mut self, val: u16) {
+        self.frame_delay = val
+    }
+
+    pub fn set_downscale(&mut self, val: u32) {
+        self.downscale = val.max(1)
+    }
+
+    // Methods
+    pub fn start(&mut self) {
+        self.frames.clear();
+        self.recording = true;
+    }
+
+    // Writes the GIF, nothing is written if no frames were recorded
+    pub fn stop(&mut self) -> io::Result<()> {
+        self.recording = false;
+        let frames = std::mem::take(&mut self.frames);
+        match frames.first() {
+            Some(first) => write_gif(&self.path, &frames, *first.size(), self.frame_delay),
+            None => Ok(()),
+        }
+    }
+
+    pub fn toggle(&mut self) -> io::Result<()> {
+        if self.recording {
+            self.stop()
+        } else {
+            self.start();
+            Ok(())
+        }
+    }
+
+    // Does nothing unless recording, frames after the window was resized are skipped since a GIF has a single size
+    pub fn record(&mut self, frame_buffer: &FrameBuffer) {
+        if !self.recording || self.frames.len() >= self.max_frames {
+            return;
+        }
+        let frame = frame_buffer.downscaled(self.downscale);
+        if self.frames.first().is_none_or(|x| x.size() == frame.size()) {
+            self.frames.push(frame)
+        }
+    }
+}
+
+fn write_gif(path: &Path, frames: &[FrameBuffer], size: Vector2D<u32>, frame_delay: u16) -> io::Result<()> {
+    let palette = palette(frames);
+    let flat_palette: Vec<u8> = palette.iter().flat_map(|x| [x.r, x.g, x.b]).collect();
+
+    let file = io::BufWriter::new(fs::File::create(path)?);
+    let mut encoder = gif::Encoder::new(file, size.x as u16, size.y as u16, &flat_palette).map_err(io::Error::other)?;
+    encoder.set_repeat(gif::Repeat::Infinite).map_err(io::Error::other)?;
+
+    // Neighbouring pixels and frames mostly share colors, so the nearest palette entry is only searched once per color
+    let mut nearest: HashMap<u32, u8> = HashMap::new();
+    for frame_buffer in frames {
+        let indices: Vec<u8> = frame_buffer
+            .buffer()
+            .iter()
+            .map(|color| {
+                *nearest.entry(color.to_u32()).or_insert_with(|| {
+                    palette
+                        .iter()
+                        .enumerate()
+                        .min_by_key(|(_, x)| color_distance(**x, *color))
+                        .map(|(i, _)| i as u8)
+                        .unwrap_or(0)
+                })
+            })
+            .collect();
+        let frame = gif::Frame {
+            width: size.x as u16,
+            height: size.y as u16,
+            delay: frame_delay,
+            buffer: Cow::Owned(indices),
+            ..gif::Frame::default()
+        };
+        encoder.write_frame(&frame).map_err(io::Error::other)?;
+    }
+    Ok(())
+}
+
+// GIFs are limited to 256 colors. Colors are grouped by dropping the lowest bits of every channel so that nearly
+// identical shades don't crowd out the rest, and the average colors of the most common groups over all frames are used
+fn palette(frames: &[FrameBuffer]) -> Vec<Color> {
+    let mut groups: HashMap<(u8, u8, u8), (u64, [u64; 3])> = HashMap::new();
+    for color in frames.iter().flat_map(|x| x.buffer()) {
+        let (count, sum) = groups
+            .entry((color.r & 0xF8, color.g & 0xF8, color.b & 0xF8))
+            .or_insert((0, [0; 3]));
+        *count += 1;
+        sum[0] += color.r as u64;
+        sum[1] += color.g as u64;
+        sum[2] += color.b as u64;
+    }
+    groups
+        .into_iter()
+        .sorted_by_key(|(group, (count, _))| (Reverse(*count), *group))
+        .take(256)
+        .map(|(_, (count, sum))| Color::new((sum[0] / count) as u8, (sum[1] / count) as u8, (sum[2] / count) as u8))
+        .collect()
+}
+
+fn color_distance(a: Color, b: Color) -> u32 {
+    [(a.r, b.r), (a.g, b.g), (a.b, b.b)]
+        .iter()
+        .map(|(x, y)| (*x as i32 - *y as i32).pow(2) as u32)
+        .sum()
+}
@@ -0,0 +1,401 @@
+use super::super::graphics::{Color, ThemeName};
+use super::super::simulation::{
+    BoundaryMode, Bounds, CollisionMode, ColorMode, Integrator, PredictionMode, Scalar, TrailStyle,
+};
+
+use clap::Parser;
+use serde::de::DeserializeOwned;
+use std::str::FromStr;
+use vector2d::Vector2D;
+
+// Largest gravitational constant either way, far past anything the keys or the panel reach
+const MAX_GRAV_CONST: Scalar = 1000.0;
+
+// ----------------------------------------------------------------
+
+// A value that can also be given as none to clear the setting
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct OrNone<T>(pub Option<T>);
+
+// ----------------------------------------------------------------
+
+// Every option is None unless it was given, Settings::apply_args only changes the settings that were
+#[derive(Debug, Clone, Default, PartialEq, Parser)]
+#[command(
+    name = "frame_buffer",
+    about = "An N-body gravity simulation",
+    after_help = "Options given here only last for this run. Settings changed while running are saved to settings.json \
+                  when the window is closed and become the new defaults."
+)]
+pub struct Args {
+    #[arg(
+        long,
+        value_name = "N",
+        help_heading = "Simulation",
+        help = "Number of random bodies to start with"
+    )]
+    pub bodies: Option<usize>,
+    #[arg(
+        long,
+        value_name = "SEED|none",
+        value_parser = or_none::<u64>,
+        help_heading = "Simulation",
+        help = "Seed for the random bodies, the same seed gives the same scene"
+    )]
+    pub seed: Option<OrNone<u64>>,
+    #[arg(
+        long,
+        value_name = "G",
+        value_parser = grav_const,
+        allow_negative_numbers = true,
+        help_heading = "Simulation",
+        help = "Gravitational constant, bodies repel each other if it is negative"
+    )]
+    pub grav_const: Option<Scalar>,
+    #[arg(
+        long,
+        value_name = "TICKS",
+        help_heading = "Simulation",
+        help = "Physics ticks per frame, 0 pauses"
+    )]
+    pub physics_speed: Option<u32>,
+    #[arg(
+        long,
+        value_name = "FACTOR",
+        value_parser = positive::<Scalar>,
+        help_heading = "Simulation",
+        help = "Multiplies the length of every tick, below 1 for slow motion"
+    )]
+    pub time_scale: Option<Scalar>,
+    #[arg(
+        long,
+        value_name = "MODE",
+        value_parser = named::<CollisionMode>,
+        help_heading = "Simulation",
+        help = "None, Collide, Absorb, Delete or Shatter"
+    )]
+    pub collision_mode: Option<CollisionMode>,
+    #[arg(
+        long,
+        value_name = "FACTOR",
+        value_parser = fraction,
+        help_heading = "Simulation",
+        help = "Bounciness of collisions, from 0 for none to 1 for perfectly elastic"
+    )]
+    pub restitution: Option<Scalar>,
+    #[arg(
+        long,
+        value_name = "FRACTION",
+        value_parser = fraction,
+        help_heading = "Simulation",
+        help = "Fraction of its velocity every body loses per tick"
+    )]
+    pub damping: Option<Scalar>,
+    #[arg(
+        long,
+        value_name = "INTEGRATOR",
+        value_parser = named::<Integrator>,
+        help_heading = "Simulation",
+        help = "Euler or VelocityVerlet"
+    )]
+    pub integrator: Option<Integrator>,
+    #[arg(
+        long,
+        value_name = "THETA",
+        help_heading = "Simulation",
+        help = "Use the Barnes-Hut approximation with this opening angle, unless bodies wrap around the bounds"
+    )]
+    pub barnes_hut_theta: Option<Scalar>,
+    #[arg(
+        long,
+        value_name = "LENGTH",
+        help_heading = "Simulation",
+        help = "Plummer softening length"
+    )]
+    pub softening: Option<Scalar>,
+    #[arg(
+        long,
+        value_name = "X,Y,W,H|none",
+        value_parser = or_none_with(bounds),
+        allow_hyphen_values = true,
+        help_heading = "Simulation",
+        help = "Rectangle bodies are kept inside of"
+    )]
+    pub bounds: Option<OrNone<Bounds>>,
+    #[arg(
+        long,
+        value_name = "MODE",
+        value_parser = named::<BoundaryMode>,
+        help_heading = "Simulation",
+        help = "None, Bounce or Wrap"
+    )]
+    pub boundary_mode: Option<BoundaryMode>,
+    #[arg(
+        long,
+        value_name = "MODE",
+        value_parser = named::<PredictionMode>,
+        help_heading = "Simulation",
+        help = "FrozenField or FullNBody"
+    )]
+    pub prediction_mode: Option<PredictionMode>,
+    #[arg(
+        long,
+        value_name = "N|none",
+        value_parser = or_none::<usize>,
+        help_heading = "Simulation",
+        help = "Maximum number of trail points per body"
+    )]
+    pub trail_length: Option<OrNone<usize>>,
+    #[arg(
+        long,
+        value_name = "STYLE",
+        value_parser = named::<TrailStyle>,
+        help_heading = "Simulation",
+        help = "Lines, Dots or None"
+    )]
+    pub trail_style: Option<TrailStyle>,
+    #[arg(
+        long,
+        value_name = "SPEED",
+        help_heading = "Simulation",
+        help = "Largest velocity kick given by perturbing"
+    )]
+    pub perturb_magnitude: Option<Scalar>,
+    #[arg(long, value_name = "SEED", help_heading = "Simulation", help = "Seed for perturbing")]
+    pub perturb_seed: Option<u64>,
+    #[arg(
+        long,
+        value_name = "true|false",
+        help_heading = "Simulation",
+        help = "Stop gravity from moving a body while it is dragged with the mouse"
+    )]
+    pub freeze_dragged: Option<bool>,
+
+    #[arg(
+        long,
+        value_name = "PIXELS",
+        value_parser = positive::<usize>,
+        help_heading = "Display",
+        help = "Window width"
+    )]
+    pub width: Option<usize>,
+    #[arg(
+        long,
+        value_name = "PIXELS",
+        value_parser = positive::<usize>,
+        help_heading = "Display",
+        help = "Window height"
+    )]
+    pub height: Option<usize>,
+    #[arg(
+        long,
+        value_name = "THEME",
+        value_parser = named::<ThemeName>,
+        help_heading = "Display",
+        help = "Dark, Light, HighContrast or RetroAmber"
+    )]
+    pub theme: Option<ThemeName>,
+    #[arg(
+        long,
+        value_name = "R,G,B|none",
+        value_parser = or_none_with(color),
+        help_heading = "Display",
+        help = "Background color instead of the theme's"
+    )]
+    pub background: Option<OrNone<Color>>,
+    #[arg(
+        long,
+        value_name = "MODE",
+        value_parser = named::<ColorMode>,
+        help_heading = "Display",
+        help = "Fixed, Speed, Mass or Group"
+    )]
+    pub color_mode: Option<ColorMode>,
+    #[arg(
+        long,
+        value_name = "true|false",
+        help_heading = "Display",
+        help = "Draw smooth lines"
+    )]
+    pub antialias: Option<bool>,
+    #[arg(
+        long,
+        value_name = "true|false",
+        help_heading = "Display",
+        help = "Draw a grid in world space"
+    )]
+    pub grid: Option<bool>,
+    #[arg(
+        long,
+        value_name = "LENGTH",
+        value_parser = positive::<f32>,
+        help_heading = "Display",
+        help = "Distance between grid lines, multiplied by powers of ten when zooming"
+    )]
+    pub grid_spacing: Option<f32>,
+    #[arg(
+        long,
+        value_name = "true|false",
+        help_heading = "Display",
+        help = "Draw arrows along the gravitational field"
+    )]
+    pub field: Option<bool>,
+    #[arg(
+        long,
+        value_name = "true|false",
+        help_heading = "Display",
+        help = "Show every body on a small map in the corner"
+    )]
+    pub minimap: Option<bool>,
+    #[arg(
+        long,
+        value_name = "true|false",
+        help_heading = "Display",
+        help = "Make bright bodies glow"
+    )]
+    pub bloom: Option<bool>,
+    #[arg(
+        long,
+        value_name = "N",
+        help_heading = "Display",
+        help = "Field arrows across the width of the window"
+    )]
+    pub field_density: Option<u32>,
+    #[arg(
+        long,
+        value_name = "PIXELS",
+        help_heading = "Display",
+        help = "Smallest radius bodies are drawn with"
+    )]
+    pub min_body_radius: Option<f32>,
+    #[arg(
+        long,
+        value_name = "FACTOR",
+        help_heading = "Display",
+        help = "Fraction of the distance to the selected body the camera moves each frame"
+    )]
+    pub camera_smoothing: Option<f32>,
+
+    #[arg(
+        long,
+        value_name = "PATH",
+        help_heading = "Other",
+        help = "Start this run from the settings in a TOML file instead of settings.json"
+    )]
+    pub config: Option<String>,
+    #[arg(
+        long,
+        value_name = "PATH",
+        help_heading = "Other",
+        help = "Directory recorded frames are written to"
+    )]
+    pub record_dir: Option<String>,
+    #[arg(
+        long,
+        value_name = "PATH",
+        help_heading = "Other",
+        help = "File recorded GIFs are written to"
+    )]
+    pub gif_path: Option<String>,
+    #[arg(
+        long,
+        value_name = "N",
+        help_heading = "Other",
+        help = "Frames kept for a GIF, later ones are dropped"
+    )]
+    pub gif_max_frames: Option<usize>,
+    #[arg(
+        long,
+        value_name = "DELAY",
+        help_heading = "Other",
+        help = "Time between GIF frames in hundredths of a second"
+    )]
+    pub gif_frame_delay: Option<u16>,
+    #[arg(
+        long,
+        value_name = "FACTOR",
+        value_parser = positive::<u32>,
+        help_heading = "Other",
+        help = "GIF frames are this many times smaller than the window"
+    )]
+    pub gif_downscale: Option<u32>,
+    #[arg(
+        long,
+        value_name = "STEPS",
+        help_heading = "Other",
+        help = "Run STEPS ticks without a window and print the bodies"
+    )]
+    pub headless: Option<usize>,
+    #[arg(
+        long,
+        value_name = "PATH",
+        help_heading = "Other",
+        help = "Write the position, mass and speed of every body after each tick to a CSV file"
+    )]
+    pub csv_log: Option<String>,
+    #[arg(long, help_heading = "Other", help = "Draw to the terminal instead of a window")]
+    pub console: bool,
+}
+
+// ----------------------------------------------------------------
+
+// Names as they are saved in the settings file
+fn named<T: DeserializeOwned>(value: &str) -> Result<T, String> {
+    serde_json::from_str(&format!("{value:?}")).map_err(|_| format!("unknown name {value:?}"))
+}
+
+fn positive<T: FromStr + PartialOrd + Default>(value: &str) -> Result<T, String> {
+    match value.parse() {
+        Ok(v) if v > T::default() => Ok(v),
+        Ok(_) => Err("must be above 0".to_string()),
+        Err(_) => Err(format!("{value:?} is not a number")),
+    }
+}
+
+fn fraction(value: &str) -> Result<Scalar, String> {
+    match value.parse::<Scalar>() {
+        Ok(v) if (0.0..=1.0).contains(&v) => Ok(v),
+        Ok(_) => Err("must be from 0 to 1".to_string()),
+        Err(_) => Err(format!("{value:?} is not a number")),
+    }
+}
+
+fn grav_const(value: &str) -> Result<Scalar, String> {
+    match value.parse::<Scalar>() {
+        Ok(v) if v.abs() <= MAX_GRAV_CONST => Ok(v),
+        Ok(_) => Err(format!("must be from -{MAX_GRAV_CONST} to {MAX_GRAV_CONST}")),
+        Err(_) => Err(format!("{value:?} is not a number")),
+    }
+}
+
+// x,y,width,height
+fn bounds(value: &str) -> Result<Bounds, String> {
+    match value.split(',').map(|x| x.parse()).collect::<Result<Vec<Scalar>, _>>() {
+        Ok(v) if v.len() == 4 => Ok(Bounds::new(
+            Vector2D::new(v[0], v[1]),
+            Vector2D::new(v[0] + v[2], v[1] + v[3]),
+        )),
+        _ => Err("must be four numbers x,y,width,height".to_string()),
+    }
+}
+
+// r,g,b
+fn color(value: &str) -> Result<Color, String> {
+    match value.split(',').map(|x| x.parse()).collect::<Result<Vec<u8>, _>>() {
+        Ok(v) if v.len() == 3 => Ok(Color::new(v[0], v[1], v[2])),
+        _ => Err("must be three numbers from 0 to 255 r,g,b".to_string()),
+    }
+}
+
+fn or_none<T: FromStr>(value: &str) -> Result<OrNone<T>, String> {
+    or_none_with(|x: &str| x.parse().map_err(|_| format!("{x:?} is not a number or none")))(value)
+}
+
+fn or_none_with<T>(
+    parse: impl Fn(&str) -> Result<T, String> + Clone + Send + Sync + 'static,
+) -> impl Fn(&str) -> Result<OrNone<T>, String> + Clone + Send + Sync + 'static {
+    move |value| match value {
+        "none" => Ok(OrNone(None)),
+        _ => parse(value).map(|v| OrNone(Some(v))),
+    }
+}
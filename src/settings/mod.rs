@@ -0,0 +1,249 @@
+use super::graphics::{Color, ThemeName, DEFAULT_FIELD_DENSITY, DEFAULT_GRID_SPACING, DEFAULT_MIN_BODY_RADIUS};
+use super::keymap::KeymapConfig;
+use super::simulation::{
+    BoundaryMode, Bounds, CollisionMode, CollisionVisuals, ColorMode, GravityMode, Integrator, PredictionMode, Scalar,
+    TrailStyle, DEFAULT_GRAV_CONST, DEFAULT_MAX_TRAIL_LENGTH, DEFAULT_RESTITUTION, DEFAULT_SOFTENING,
+};
+#[cfg(feature = "native")]
+pub use args::{Args, OrNone};
+
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::Path;
+
+#[cfg(feature = "native")]
+mod args;
+
+pub const SETTINGS_PATH: &str = "settings.json";
+
+const DEFAULT_WIDTH: usize = 1260;
+const DEFAULT_HEIGHT: usize = 720;
+const DEFAULT_PERTURB_MAGNITUDE: Scalar = 0.1;
+const DEFAULT_CAMERA_SMOOTHING: f32 = 0.1;
+const DEFAULT_NUM_OF_BODIES: usize = 10;
+const DEFAULT_RECORD_DIR: &str = "frames";
+const DEFAULT_GIF_PATH: &str = "recording.gif";
+const DEFAULT_GIF_MAX_FRAMES: usize = 600;
+const DEFAULT_GIF_FRAME_DELAY: u16 = 2;
+const DEFAULT_GIF_DOWNSCALE: u32 = 2;
+
+// ----------------------------------------------------------------
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Settings {
+    pub grav_const: Scalar,
+    pub physics_speed: u32,
+    pub time_scale: Scalar,
+    pub collision_mode: CollisionMode,
+    pub restitution: Scalar,
+    pub damping: Scalar,
+    pub gravity_mode: GravityMode,
+    pub integrator: Integrator,
+    pub softening: Scalar,
+    pub bounds: Option<Bounds>,
+    pub boundary_mode: BoundaryMode,
+    pub max_trail_length: Option<usize>,
+    pub trail_style: TrailStyle,
+    pub prediction_mode: PredictionMode,
+    pub color_mode: ColorMode,
+    pub width: usize,
+    pub height: usize,
+    pub min_body_radius: f32,
+    pub perturb_magnitude: Scalar,
+    pub perturb_seed: u64,
+    pub freeze_dragged_body: bool,
+    pub num_of_bodies: usize,
+    // Seed for the initial bodies, a different scene every run if None
+    pub seed: Option<u64>,
+    pub theme: ThemeName,
+    // Replaces the theme's background if set
+    pub background: Option<Color>,
+    pub antialias: bool,
+    pub show_grid: bool,
+    pub grid_spacing: f32,
+    pub show_field: bool,
+    pub field_density: u32,
+    pub show_minimap: bool,
+    pub bloom: bool,
+    // Fraction of the distance to the selected body the camera moves each frame
+    pub camera_smoothing: f32,
+    // Where the frames are written while recording
+    pub record_dir: String,
+    pub gif_path: String,
+    pub gif_max_frames: usize,
+    // In hundredths of a second
+    pub gif_frame_delay: u16,
+    pub gif_downscale: u32,
+    // Overrides for the default key bindings, see keymap::Keymap::from_config
+    pub keymap: KeymapConfig,
+    pub collision_visuals: CollisionVisuals,
+    // Only set from the command line, steps the simulation without a window and prints the bodies
+    #[serde(skip)]
+    pub headless_steps: Option<usize>,
+    // Only set from the command line, so a later run doesn't overwrite the file without asking for it
+    #[serde(skip)]
+    pub csv_log: Option<String>,
+    // Only set from the command line, draws to the terminal instead of a window
+    #[serde(skip)]
+    pub console: bool,
+}
+
+impl Default for Settings {
+    fn default() -> Self {
+        Settings {
+            grav_const: DEFAULT_GRAV_CONST,
+            physics_speed: 1,
+            time_scale: 1.0,
+            collision_mode: CollisionMode::None,
+            restitution: DEFAULT_RESTITUTION,
+            damping: 0.0,
+            gravity_mode: GravityMode::Direct,
+            integrator: Integrator::Euler,
+            softening: DEFAULT_SOFTENING,
+            bounds: None,
+            boundary_mode: BoundaryMode::None,
+            max_trail_length: DEFAULT_MAX_TRAIL_LENGTH,
+            trail_style: TrailStyle::Lines,
+            prediction_mode: PredictionMode::FullNBody,
+            color_mode: ColorMode::Fixed,
+            width: DEFAULT_WIDTH,
+            height: DEFAULT_HEIGHT,
+            min_body_radius: DEFAULT_MIN_BODY_RADIUS,
+            perturb_magnitude: DEFAULT_PERTURB_MAGNITUDE,
+            perturb_seed: 0,
+            freeze_dragged_body: true,
+            num_of_bodies: DEFAULT_NUM_OF_BODIES,
+            seed: None,
+            theme: ThemeName::Dark,
+            background: None,
+            antialias: false,
+            show_grid: false,
+            grid_spacing: DEFAULT_GRID_SPACING,
+            show_field: false,
+            field_density: DEFAULT_FIELD_DENSITY,
+            show_minimap: false,
+            bloom: false,
+            camera_smoothing: DEFAULT_CAMERA_SMOOTHING,
+            record_dir: DEFAULT_RECORD_DIR.to_string(),
+            gif_path: DEFAULT_GIF_PATH.to_string(),
+            gif_max_frames: DEFAULT_GIF_MAX_FRAMES,
+            gif_frame_delay: DEFAULT_GIF_FRAME_DELAY,
+            gif_downscale: DEFAULT_GIF_DOWNSCALE,
+            keymap: KeymapConfig::new(),
+            collision_visuals: CollisionVisuals::default(),
+            headless_steps: None,
+            csv_log: None,
+            console: false,
+        }
+    }
+}
+
+#[allow(dead_code)]
+impl Settings {
+    // Constructor
+    pub fn load(path: impl AsRef<Path>) -> Settings {
+        let path = path.as_ref();
+        match fs::read_to_string(path) {
+            Ok(contents) => match serde_json::from_str(&contents) {
+                Ok(settings) => settings,
+                Err(e) => {
+                    eprintln!("Ignoring corrupt settings file {:?}: {e}", path);
+                    Settings::default()
+                }
+            },
+            Err(_) => Settings::default(),
+        }
+    }
+
+    // Named experiment setups, anything missing from the file is left at its default
+    pub fn load_config(path: impl AsRef<Path>) -> std::io::Result<Settings> {
+        toml::from_str(&fs::read_to_string(path)?).map_err(|e| std::io::Error::new(std::io::ErrorKind::InvalidData, e))
+    }
+
+    // Methods
+    pub fn save(&self, path: impl AsRef<Path>) -> std::io::Result<()> {
+        fs::write(path, serde_json::to_string_pretty(self)?)
+    }
+
+    // Takes every setting that is different in to than in from. Changes made while running can be saved this way
+    // without also saving the options the run was started with
+    pub fn apply_changes(&mut self, from: &Settings, to: &Settings) {
+        use serde_json::Value;
+
+        if let (Ok(Value::Object(from)), Ok(Value::Object(to)), Ok(Value::Object(mut merged))) = (
+            serde_json::to_value(from),
+            serde_json::to_value(to),
+            serde_json::to_value(&*self),
+        ) {
+            to.into_iter()
+                .filter(|(key, value)| from.get(key) != Some(value))
+                .for_each(|(key, value)| {
+                    merged.insert(key, value);
+                });
+            if let Ok(settings) = serde_json::from_value(Value::Object(merged)) {
+                *self = settings
+            }
+        }
+    }
+
+    // Only the options that were given change anything. A config file replaces the settings before the other
+    // options, so they always override it
+    #[cfg(feature = "native")]
+    pub fn apply_args(&mut self, args: Args) -> std::io::Result<()> {
+        if let Some(path) = &args.config {
+            *self = Settings::load_config(path)?;
+        }
+
+        fn set<T>(setting: &mut T, arg: Option<T>) {
+            if let Some(v) = arg {
+                *setting = v
+            }
+        }
+        set(&mut self.num_of_bodies, args.bodies);
+        set(&mut self.seed, args.seed.map(|x| x.0));
+        set(&mut self.grav_const, args.grav_const);
+        set(&mut self.physics_speed, args.physics_speed);
+        set(&mut self.time_scale, args.time_scale);
+        set(&mut self.collision_mode, args.collision_mode);
+        set(&mut self.restitution, args.restitution);
+        set(&mut self.damping, args.damping);
+        set(&mut self.integrator, args.integrator);
+        set(
+            &mut self.gravity_mode,
+            args.barnes_hut_theta.map(|theta| GravityMode::BarnesHut { theta }),
+        );
+        set(&mut self.softening, args.softening);
+        set(&mut self.bounds, args.bounds.map(|x| x.0));
+        set(&mut self.boundary_mode, args.boundary_mode);
+        set(&mut self.prediction_mode, args.prediction_mode);
+        set(&mut self.max_trail_length, args.trail_length.map(|x| x.0));
+        set(&mut self.trail_style, args.trail_style);
+        set(&mut self.perturb_magnitude, args.perturb_magnitude);
+        set(&mut self.perturb_seed, args.perturb_seed);
+        set(&mut self.freeze_dragged_body, args.freeze_dragged);
+        set(&mut self.width, args.width);
+        set(&mut self.height, args.height);
+        set(&mut self.theme, args.theme);
+        set(&mut self.background, args.background.map(|x| x.0));
+        set(&mut self.color_mode, args.color_mode);
+        set(&mut self.antialias, args.antialias);
+        set(&mut self.show_grid, args.grid);
+        set(&mut self.grid_spacing, args.grid_spacing);
+        set(&mut self.show_field, args.field);
+        set(&mut self.show_minimap, args.minimap);
+        set(&mut self.bloom, args.bloom);
+        set(&mut self.field_density, args.field_density);
+        set(&mut self.min_body_radius, args.min_body_radius);
+        set(&mut self.camera_smoothing, args.camera_smoothing);
+        set(&mut self.record_dir, args.record_dir);
+        set(&mut self.gif_path, args.gif_path);
+        set(&mut self.gif_max_frames, args.gif_max_frames);
+        set(&mut self.gif_frame_delay, args.gif_frame_delay);
+        set(&mut self.gif_downscale, args.gif_downscale);
+        set(&mut self.headless_steps, args.headless.map(Some));
+        set(&mut self.csv_log, args.csv_log.map(Some));
+        self.console |= args.console;
+        Ok(())
+    }
+}
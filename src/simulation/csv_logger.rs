@@ -0,0 +1,88 @@
+use super::PhysicsBody;
+
+use std::fs::File;
+use std::io::{self, BufWriter, Write};
+use std::path::{Path, PathBuf};
+
+const HEADER: &str = "tick,body,x,y,mass,speed";
+// Rows are buffered in between, so at most this many ticks are lost if the program crashes
+const FLUSH_INTERVAL: u64 = 100;
+
+// ----------------------------------------------------------------
+
+// Appends every body's state after each physics tick to a CSV file, for plotting orbits or checking conservation
+#[derive(Debug)]
+pub struct CsvLogger {
+    path: PathBuf,
+    // None for clones, which don't write anything
+    writer: Option<BufWriter<File>>,
+    tick: u64,
+}
+
+#[allow(dead_code)]
+impl CsvLogger {
+    // Constructor
+    // Replaces the file if it already exists
+    pub fn create(path: impl AsRef<Path>) -> io::Result<CsvLogger> {
+        let mut writer = BufWriter::new(File::create(&path)?);
+        writeln!(writer, "{HEADER}")?;
+        Ok(CsvLogger {
+            path: path.as_ref().to_path_buf(),
+            writer: Some(writer),
+            tick: 0,
+        })
+    }
+
+    // Immutable access
+    pub fn path(&self) -> &PathBuf {
+        &self.path
+    }
+
+    pub fn tick(&self) -> &u64 {
+        &self.tick
+    }
+
+    // Methods
+    // Rows are numbered by the ticks logged so far, starting at 1. is_multiple_of needs a newer compiler than the
+    // rest of the crate
+    #[allow(clippy::manual_is_multiple_of)]
+    pub fn log(&mut self, bodies: &[PhysicsBody]) -> io::Result<()> {
+        self.tick += 1;
+        if let Some(writer) = &mut self.writer {
+            for (i, body) in bodies.iter().enumerate() {
+                writeln!(
+                    writer,
+                    "{},{},{},{},{},{}",
+                    self.tick,
+                    i,
+                    body.pos().x,
+                    body.pos().y,
+                    body.mass(),
+                    body.velocity().speed()
+                )?;
+            }
+            if self.tick % FLUSH_INTERVAL == 0 {
+                writer.flush()?;
+            }
+        }
+        Ok(())
+    }
+
+    pub fn flush(&mut self) -> io::Result<()> {
+        match &mut self.writer {
+            Some(writer) => writer.flush(),
+            None => Ok(()),
+        }
+    }
+}
+
+// A copy of a simulation stepped ahead on its own shouldn't add rows to the same file
+impl Clone for CsvLogger {
+    fn clone(&self) -> Self {
+        CsvLogger {
+            path: self.path.clone(),
+            writer: None,
+            tick: self.tick,
+        }
+    }
+}
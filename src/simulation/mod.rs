@@ -1,24 +1,382 @@
 use super::graphics;
+pub use csv_logger::CsvLogger;
+use quadtree::QuadTree;
+use spatial_hash::SpatialHash;
 
 use itertools::Itertools;
 use rand::prelude::*;
+#[cfg(feature = "parallel")]
+use rayon::prelude::*;
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet, VecDeque};
 use std::fmt;
+use std::fs;
+use std::io;
+use std::path::Path;
 use vector2d::Vector2D;
 
-const DEFAULT_GRAV_CONST: f32 = 0.005;
-const MAX_FORCE_AMPLITUDE: Option<f32> = Some(10.0);
-const MAX_TRAIL_LENGTH: Option<usize> = Some(1000);
+mod csv_logger;
+mod quadtree;
+mod serde_vector2d;
+mod spatial_hash;
+
+// Floating point type of the physics, enable the f64 feature for less drift in long runs. Drawing stays in f32
+#[cfg(not(feature = "f64"))]
+pub type Scalar = f32;
+#[cfg(feature = "f64")]
+pub type Scalar = f64;
+
+// Drawing is always done in f32, this is a no-op cast without the f64 feature
+#[allow(clippy::unnecessary_cast)]
+pub fn to_f32(val: Scalar) -> f32 {
+    val as f32
+}
+
+pub fn to_f32_vec(val: Vector2D<Scalar>) -> Vector2D<f32> {
+    Vector2D::new(to_f32(val.x), to_f32(val.y))
+}
+
+pub fn from_f32_vec(val: Vector2D<f32>) -> Vector2D<Scalar> {
+    Vector2D::from_vec2d(val)
+}
 
-const NUM_OF_BODIES: usize = 10;
+// Gravity changes a body's velocity by grav_const times the other body's mass over the distance squared. Before
+// dividing by the body's own mass this was 0.005, random bodies average a mass of 25 so 0.1 keeps scenes as lively
+pub const DEFAULT_GRAV_CONST: Scalar = 0.1;
+// The gravitational constant is multiplied or divided by this per key press, keeping its sign
+const GRAV_CONST_STEP: Scalar = 1.1;
+const MAX_ACCELERATION: Option<Scalar> = Some(10.0);
+pub const DEFAULT_MAX_TRAIL_LENGTH: Option<usize> = Some(1000);
+const TRAIL_LENGTH_PRESETS: [Option<usize>; 5] = [Some(0), Some(100), Some(1000), Some(5000), None];
+
+const PREDICTION_STEPS: usize = 300;
+const PREDICTION_ENSEMBLE_SIZE: usize = 8;
+const MAX_PREDICTION_ENSEMBLE_SIZE: usize = 32;
+const DEFAULT_PREDICTION_PERTURBATION: Scalar = 0.05;
+// Opacity of each path of the prediction cone, where the paths overlap they add up
+const PREDICTION_CONE_ALPHA: f32 = 0.2;
+pub const DEFAULT_RESTITUTION: Scalar = 1.0;
+// Change in restitution per key press
+const RESTITUTION_STEP: Scalar = 0.1;
+// Bodies approaching each other faster than this break apart in CollisionMode::Shatter, slower ones bounce
+const SHATTER_SPEED: Scalar = 1.0;
+const SHATTER_FRAGMENTS: usize = 6;
+// Speed the fragments fly apart with per unit of the speed the bodies hit each other with
+const SHATTER_SPREAD: Scalar = 0.5;
+// Bodies bounce instead of breaking once shattering would make there be more bodies than this
+const MAX_SHATTER_BODIES: usize = 500;
+// Ticks a collision flash takes to fade out, and how many can be shown at once
+const FLASH_TICKS: u32 = 20;
+const MAX_FLASHES: usize = 64;
+// Radius of a flash per unit of the radius a body with the pair's reduced mass would have
+const FLASH_SIZE: Scalar = 2.0;
+#[allow(clippy::unnecessary_cast)]
+const TAU: Scalar = std::f64::consts::TAU as Scalar;
+const DEFAULT_WALL_RESTITUTION: Scalar = 1.0;
+const DEFAULT_DT: Scalar = 1.0;
+const MIN_TIME_SCALE: Scalar = 1.0 / 64.0;
+const MAX_TIME_SCALE: Scalar = 64.0;
+const DEFAULT_BARNES_HUT_THETA: Scalar = 0.5;
+pub const DEFAULT_SOFTENING: Scalar = 1.0;
+
+// Length of a body's velocity arrow per unit of velocity, dragging to place a body uses the same scale
+const VELOCITY_ARROW_SCALE: Scalar = 20.0;
+// Fraction of the way the velocity given to a dragged body moves towards the cursor's velocity each frame
+const DRAG_VELOCITY_SMOOTHING: Scalar = 0.5;
+// Size of the head on velocity arrows, shrunk on arrows shorter than twice its length
+const ARROWHEAD_LENGTH: Scalar = 6.0;
+const ARROWHEAD_WIDTH: Scalar = 5.0;
+// Length of a body's net force arrow per unit of force, capped so strong forces don't cross the whole screen
+const FORCE_ARROW_SCALE: Scalar = 1000.0;
+const MAX_FORCE_ARROW_LENGTH: Scalar = 60.0;
+// Field arrows reach half of their longest length at this acceleration
+const FIELD_ARROW_HALF_ACCELERATION: Scalar = 0.01;
+const CENTER_OF_MASS_MARKER_SIZE: Scalar = 8.0;
+// Gap between the selected body and the ring drawn around it
+const SELECTION_RING_GAP: Scalar = 3.0;
+const MASS_PER_RADIUS: Scalar = 5.0;
+const DEFAULT_BODY_MASS: Scalar = 25.0;
+// Every step of the scroll wheel multiplies the mass by this, down to MIN_BODY_MASS
+const MASS_SCROLL_FACTOR: Scalar = 1.1;
+const MIN_BODY_MASS: Scalar = 1.0;
+// A duplicated body is placed this many radii to the right of the original so the two don't collide straight away
+const DUPLICATE_OFFSET: Scalar = 2.5;
+// Number of edits that can be undone, older ones are forgotten
+const MAX_UNDO_HISTORY: usize = 100;
+// Presets are laid out around the middle of the default window
+const PRESET_CENTER: Vector2D<Scalar> = Vector2D { x: 630.0, y: 360.0 };
+const FIGURE_EIGHT_SCALE: Scalar = 200.0;
+const FIGURE_EIGHT_PERIOD_TICKS: Scalar = 2000.0;
+const FIGURE_EIGHT_MASS: Scalar = 50.0;
+const ORBITS_CENTRAL_MASS: Scalar = 200.0;
+// Light enough that neighbouring orbits barely pull each other out of shape, the bodies are drawn at the smallest
+// size either way
+const ORBITS_BODY_MASS: Scalar = 0.1;
+const ORBITS_FIRST_RADIUS: Scalar = 80.0;
+const ORBITS_SPACING: Scalar = 40.0;
+const SLOW_COLOR: graphics::Color = graphics::Color { r: 0, g: 0, b: 255 };
+const FAST_COLOR: graphics::Color = graphics::Color { r: 255, g: 0, b: 0 };
+const LIGHT_COLOR: graphics::Color = graphics::Color { r: 50, g: 20, b: 90 };
+const HEAVY_COLOR: graphics::Color = graphics::Color { r: 255, g: 235, b: 150 };
+// Group colors repeat past the last one
+const GROUP_COLORS: [graphics::Color; 8] = [
+    graphics::Color { r: 230, g: 80, b: 70 },
+    graphics::Color { r: 80, g: 160, b: 240 },
+    graphics::Color { r: 120, g: 210, b: 90 },
+    graphics::Color { r: 240, g: 200, b: 60 },
+    graphics::Color { r: 190, g: 110, b: 230 },
+    graphics::Color { r: 70, g: 210, b: 200 },
+    graphics::Color { r: 240, g: 140, b: 50 },
+    graphics::Color { r: 230, g: 120, b: 180 },
+];
 
 // ----------------------------------------------------------------
 
-#[derive(Debug)]
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
 pub enum CollisionMode {
     None,
     Collide,
     Absorb,
     Delete,
+    // Fast collisions break both bodies into fragments, slow ones are resolved like Collide
+    Shatter,
+}
+
+// Euler adds the acceleration to the velocity before moving, VelocityVerlet moves with the previous acceleration
+// and then corrects the velocity with the average of the previous and new acceleration
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub enum Integrator {
+    Euler,
+    VelocityVerlet,
+}
+
+// BarnesHut approximates distant groups of bodies by their center of mass, a smaller theta is more accurate
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub enum GravityMode {
+    Direct,
+    BarnesHut { theta: Scalar },
+}
+
+#[allow(dead_code)]
+impl GravityMode {
+    pub fn barnes_hut() -> GravityMode {
+        GravityMode::BarnesHut {
+            theta: DEFAULT_BARNES_HUT_THETA,
+        }
+    }
+}
+
+// What happens to bodies crossing the edge of the simulation bounds, nothing happens without bounds
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub enum BoundaryMode {
+    None,
+    Bounce,
+    Wrap,
+}
+
+// Fixed draws every body in its own color, Speed maps the speed relative to the fastest body from blue to red,
+// Mass maps the mass relative to the heaviest body from dark to bright and Group gives every group its own color
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub enum ColorMode {
+    Fixed,
+    Speed,
+    Mass,
+    Group,
+}
+
+#[allow(dead_code)]
+impl ColorMode {
+    // Methods
+    pub fn next(self) -> ColorMode {
+        match self {
+            ColorMode::Fixed => ColorMode::Speed,
+            ColorMode::Speed => ColorMode::Mass,
+            ColorMode::Mass => ColorMode::Group,
+            ColorMode::Group => ColorMode::Fixed,
+        }
+    }
+}
+
+// Lines connects the points of a trail, Dots draws every point on its own, fading with age, so fast or curvy
+// motion doesn't get its corners cut
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub enum TrailStyle {
+    Lines,
+    Dots,
+    None,
+}
+
+#[allow(dead_code)]
+impl TrailStyle {
+    // Methods
+    pub fn next(self) -> TrailStyle {
+        match self {
+            TrailStyle::Lines => TrailStyle::Dots,
+            TrailStyle::Dots => TrailStyle::None,
+            TrailStyle::None => TrailStyle::Lines,
+        }
+    }
+}
+
+// FrozenField only moves the predicted body through the field of the others at their current positions, FullNBody
+// simulates every body
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub enum PredictionMode {
+    FrozenField,
+    FullNBody,
+}
+
+// ----------------------------------------------------------------
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct Bounds {
+    #[serde(with = "serde_vector2d")]
+    min: Vector2D<Scalar>,
+    #[serde(with = "serde_vector2d")]
+    max: Vector2D<Scalar>,
+}
+
+#[allow(dead_code)]
+impl Bounds {
+    // Constructor
+    pub fn new(corner_1: Vector2D<Scalar>, corner_2: Vector2D<Scalar>) -> Bounds {
+        Bounds {
+            min: Vector2D::new(corner_1.x.min(corner_2.x), corner_1.y.min(corner_2.y)),
+            max: Vector2D::new(corner_1.x.max(corner_2.x), corner_1.y.max(corner_2.y)),
+        }
+    }
+
+    // Immutable access
+    pub fn min(&self) -> &Vector2D<Scalar> {
+        &self.min
+    }
+
+    pub fn max(&self) -> &Vector2D<Scalar> {
+        &self.max
+    }
+
+    // Methods
+    pub fn size(&self) -> Vector2D<Scalar> {
+        self.max - self.min
+    }
+
+    pub fn contains(&self, p: Vector2D<Scalar>) -> bool {
+        p.x >= self.min.x && p.x <= self.max.x && p.y >= self.min.y && p.y <= self.max.y
+    }
+
+    // Shortest version of the displacement when opposite edges are joined together
+    pub fn minimum_image(&self, displacement: Vector2D<Scalar>) -> Vector2D<Scalar> {
+        let size = self.size();
+        let wrap = |d: Scalar, size: Scalar| if size > 0.0 { d - size * (d / size).round() } else { d };
+        Vector2D::new(wrap(displacement.x, size.x), wrap(displacement.y, size.y))
+    }
+
+    pub fn shapes(&self, color: graphics::Color) -> Vec<Box<dyn graphics::Draw>> {
+        let corners = [
+            self.min,
+            Vector2D::new(self.max.x, self.min.y),
+            self.max,
+            Vector2D::new(self.min.x, self.max.y),
+        ];
+        let mut out: Vec<Box<dyn graphics::Draw>> = vec![];
+        for (start, end) in corners.iter().zip(corners.iter().cycle().skip(1)) {
+            out.push(Box::new(graphics::Line::new(
+                to_f32_vec(*start),
+                to_f32_vec(*end),
+                0,
+                color,
+            )))
+        }
+        out
+    }
+}
+
+// ----------------------------------------------------------------
+
+// Which pairs of body groups attract each other, every pair does until it is turned off. Attraction is mutual, the
+// order of the groups doesn't matter
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct GravityMatrix {
+    // Stored with the smaller group first
+    disabled: HashSet<(u8, u8)>,
+}
+
+#[allow(dead_code)]
+impl GravityMatrix {
+    // Constructor
+    pub fn new() -> GravityMatrix {
+        GravityMatrix::default()
+    }
+
+    // Immutable access
+    pub fn interacts(&self, group_1: u8, group_2: u8) -> bool {
+        !self.disabled.contains(&(group_1.min(group_2), group_1.max(group_2)))
+    }
+
+    pub fn all_interact(&self) -> bool {
+        self.disabled.is_empty()
+    }
+
+    // Setters
+    pub fn set_interacts(&mut self, group_1: u8, group_2: u8, val: bool) {
+        let pair = (group_1.min(group_2), group_1.max(group_2));
+        if val {
+            self.disabled.remove(&pair);
+        } else {
+            self.disabled.insert(pair);
+        }
+    }
+}
+
+// ----------------------------------------------------------------
+
+// How collisions look, saved with the scene so a themed scene reloads the same
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct CollisionVisuals {
+    // A disc lights up and fades where bodies hit each other, starting this far from the background to flash_color.
+    // 0.0 turns flashes off. Before flash_color as TOML wants plain values ahead of tables
+    pub flash_intensity: f32,
+    pub flash_color: graphics::Color,
+    // Color of the fragments in CollisionMode::Shatter, None keeps the colors of the bodies they broke off from
+    pub debris_color: Option<graphics::Color>,
+}
+
+impl Default for CollisionVisuals {
+    fn default() -> Self {
+        CollisionVisuals {
+            flash_intensity: 0.6,
+            flash_color: graphics::Color::new(255, 255, 255),
+            debris_color: None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+struct Flash {
+    pos: Vector2D<Scalar>,
+    radius: Scalar,
+    // Ticks since the collision
+    age: u32,
+}
+
+// ----------------------------------------------------------------
+
+// Two bodies hitting each other, in any collision mode
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct CollisionEvent {
+    // Where the bodies touch, on the line between their centers
+    pub pos: Vector2D<Scalar>,
+    // Speed at which the bodies moved towards each other
+    pub speed: Scalar,
+    // Reduced mass of the pair, the mass of the other body if one is fixed
+    pub mass: Scalar,
+}
+
+impl CollisionEvent {
+    // Kinetic energy of the motion towards each other, what a perfectly inelastic collision would lose
+    pub fn energy(&self) -> Scalar {
+        0.5 * self.mass * self.speed * self.speed
+    }
 }
 
 // ----------------------------------------------------------------
@@ -29,22 +387,109 @@ pub struct SimulationInput {
     pub remove_body: bool,
     pub print_body: bool,
     pub selected_body: bool,
+    pub delete_selected: bool,
+    // Copies the selected body next to it and selects the copy
+    pub duplicate_selected: bool,
+    pub undo: bool,
+    pub redo: bool,
 
     pub up_speed: bool,
     pub down_speed: bool,
+    pub halve_time_scale: bool,
+    pub double_time_scale: bool,
+    pub lower_restitution: bool,
+    pub raise_restitution: bool,
+    pub increase_grav_const: bool,
+    pub decrease_grav_const: bool,
+    // Negates the gravitational constant, a negative one makes bodies repel each other
+    pub flip_gravity: bool,
 
     pub reset_contents: bool,
 
+    pub toggle_prediction_cone: bool,
+    pub toggle_trail_blend_mode: bool,
+    pub toggle_trail_fade: bool,
+    pub cycle_trail_length: bool,
+    pub cycle_trail_style: bool,
+    pub clear_trails: bool,
+
+    pub load_figure_eight: bool,
+    pub load_orbits: bool,
+
+    pub toggle_fixed: bool,
+    pub toggle_center_of_mass: bool,
+    pub toggle_force_arrows: bool,
+    pub cycle_color_mode: bool,
+
+    // Pressing on a body grabs it and releasing throws it with the cursor's velocity. Pressing anywhere else starts
+    // placing a body at the cursor, releasing adds it with a velocity set by the drag
+    pub mouse_left_down: bool,
+
     pub mouse_world_pos: Option<Vector2D<f32>>,
+    // How far the cursor moved since the last frame in world units, measured on screen so moving the view doesn't
+    // count
+    pub mouse_world_delta: Option<Vector2D<f32>>,
     pub mouse_scroll_wheel: Option<f32>,
+    // Scrolling changes the mass of the body under the cursor
+    pub adjust_mass: bool,
+    pub min_pick_radius: f32,
 }
 
+// Cloning copies everything, including trails and the undo history, so a copy can be stepped ahead on its own
+#[derive(Clone)]
 pub struct Simulation {
     bodies: Vec<PhysicsBody>,
+    // Resetting brings back this many random bodies, the same ones every time if seed is set
+    num_of_bodies: usize,
+    seed: Option<u64>,
     selected_body: Option<usize>,
-    grav_const: f32,
+    grav_const: Scalar,
     physics_speed: u32,
+    dt: Scalar,
+    // Multiplies dt, below 1 for slow motion
+    time_scale: Scalar,
     collision_mode: CollisionMode,
+    gravity_mode: GravityMode,
+    integrator: Integrator,
+    max_trail_length: Option<usize>,
+    show_prediction_cone: bool,
+    prediction_mode: PredictionMode,
+    show_center_of_mass: bool,
+    show_force_arrows: bool,
+    prediction_perturbation: Scalar,
+    trail_blend_mode: graphics::BlendMode,
+    // Older trail segments fade towards the background
+    trail_fade: bool,
+    trail_style: TrailStyle,
+    color_mode: ColorMode,
+    integration_substeps: u32,
+    theme: graphics::Theme,
+    restitution: Scalar,
+    // Fraction of its velocity every body loses per tick
+    damping: Scalar,
+    softening: Scalar,
+    bounds: Option<Bounds>,
+    boundary_mode: BoundaryMode,
+    wall_restitution: Scalar,
+    // Body being placed by dragging the mouse, not simulated until the button is released
+    pending_body: Option<PhysicsBody>,
+    // Index of the body held by the mouse and whether it was fixed before it was grabbed
+    dragged_body: Option<(usize, bool)>,
+    // World units per tick, given to the dragged body when it is released
+    drag_velocity: Vector2D<Scalar>,
+    // Fixes the dragged body so gravity doesn't pull it away from the cursor
+    freeze_dragged_body: bool,
+    // Reused by gravity_tick so forces aren't allocated every tick
+    force_buffer: Vec<Vector2D<Scalar>>,
+    // Every body added, removed or merged since the bodies were last replaced as a whole, newest last
+    undo_history: VecDeque<Vec<Edit>>,
+    redo_history: Vec<Vec<Edit>>,
+    // Writes the bodies after every tick if set
+    csv_logger: Option<CsvLogger>,
+    gravity_matrix: GravityMatrix,
+    collision_visuals: CollisionVisuals,
+    // Newest last
+    flashes: Vec<Flash>,
 }
 
 #[allow(dead_code)]
@@ -52,20 +497,166 @@ impl Simulation {
     // Constructor
     pub fn new(
         bodies: Vec<PhysicsBody>,
-        grav_const: Option<f32>,
+        grav_const: Option<Scalar>,
         physics_speed: Option<u32>,
+        dt: Option<Scalar>,
         collision_mode: CollisionMode,
     ) -> Simulation {
         Simulation {
+            num_of_bodies: bodies.len(),
+            seed: None,
             bodies,
             selected_body: None,
             grav_const: grav_const.unwrap_or(DEFAULT_GRAV_CONST),
             physics_speed: physics_speed.unwrap_or(1),
+            dt: dt.unwrap_or(DEFAULT_DT),
+            time_scale: 1.0,
             collision_mode,
+            gravity_mode: GravityMode::Direct,
+            integrator: Integrator::Euler,
+            max_trail_length: DEFAULT_MAX_TRAIL_LENGTH,
+            show_prediction_cone: false,
+            prediction_mode: PredictionMode::FullNBody,
+            show_center_of_mass: false,
+            show_force_arrows: false,
+            prediction_perturbation: DEFAULT_PREDICTION_PERTURBATION,
+            trail_blend_mode: graphics::BlendMode::Replace,
+            trail_fade: false,
+            trail_style: TrailStyle::Lines,
+            color_mode: ColorMode::Fixed,
+            integration_substeps: 1,
+            theme: graphics::Theme::default(),
+            restitution: DEFAULT_RESTITUTION,
+            damping: 0.0,
+            softening: DEFAULT_SOFTENING,
+            bounds: None,
+            boundary_mode: BoundaryMode::None,
+            wall_restitution: DEFAULT_WALL_RESTITUTION,
+            pending_body: None,
+            dragged_body: None,
+            drag_velocity: Vector2D::new(0.0, 0.0),
+            freeze_dragged_body: true,
+            force_buffer: vec![],
+            undo_history: VecDeque::new(),
+            redo_history: vec![],
+            csv_logger: None,
+            gravity_matrix: GravityMatrix::new(),
+            collision_visuals: CollisionVisuals::default(),
+            flashes: vec![],
         }
     }
 
-    pub fn grav_const(&self) -> &f32 {
+    // n random bodies that are the same for every run with the same seed
+    pub fn new_seeded(seed: u64, n: usize) -> Simulation {
+        let mut simulation = Simulation::new(
+            Simulation::random_bodies(n, Some(seed)),
+            None,
+            None,
+            None,
+            CollisionMode::None,
+        );
+        simulation.set_seed(Some(seed));
+        simulation
+    }
+
+    // The same bodies every time for the same seed, different ones every time without one
+    pub fn random_bodies(n: usize, seed: Option<u64>) -> Vec<PhysicsBody> {
+        match seed {
+            Some(seed) => {
+                let mut rng = StdRng::seed_from_u64(seed);
+                (0..n).map(|_| PhysicsBody::new_rand_with(&mut rng)).collect()
+            }
+            None => (0..n).map(|_| PhysicsBody::new_rand()).collect(),
+        }
+    }
+
+    // Three equal bodies chasing each other along a figure eight (Chenciner and Montgomery), the positions and
+    // velocities are the known solution for G = m = 1 scaled to world units so a period takes
+    // FIGURE_EIGHT_PERIOD_TICKS ticks
+    pub fn preset_figure_eight() -> Simulation {
+        // Length of one period for G = m = 1
+        let period = 6.325_9;
+        let time_scale = period / FIGURE_EIGHT_PERIOD_TICKS;
+        let pos = Vector2D::new(0.970_004_4, -0.243_087_5) * FIGURE_EIGHT_SCALE;
+        let velocity = Vector2D::new(-0.932_407_4, -0.864_731_5) * (FIGURE_EIGHT_SCALE * time_scale);
+        let colors = [
+            graphics::Color::new(255, 90, 90),
+            graphics::Color::new(90, 255, 90),
+            graphics::Color::new(90, 140, 255),
+        ];
+        let bodies = [
+            (pos, velocity / -2.0),
+            (-pos, velocity / -2.0),
+            (Vector2D::new(0.0, 0.0), velocity),
+        ]
+        .into_iter()
+        .zip(colors)
+        .map(|((pos, velocity), color)| {
+            PhysicsBody::builder()
+                .pos(PRESET_CENTER + pos)
+                .mass(FIGURE_EIGHT_MASS)
+                .velocity(velocity)
+                .color(color)
+                .build()
+        })
+        .collect();
+        // G * m = L^3 / T^2 with L world units per unit length and T ticks per unit time
+        let grav_const = FIGURE_EIGHT_SCALE.powi(3) * time_scale.powi(2) / FIGURE_EIGHT_MASS;
+
+        Simulation::new(bodies, Some(grav_const), None, None, CollisionMode::None)
+    }
+
+    // A heavy body in the middle with n light bodies on circular orbits around it, each further out than the last
+    pub fn preset_orbits(n: usize) -> Simulation {
+        let grav_const = DEFAULT_GRAV_CONST;
+        let mut bodies = vec![PhysicsBody::builder()
+            .pos(PRESET_CENTER)
+            .mass(ORBITS_CENTRAL_MASS)
+            .color(graphics::Color::new(255, 220, 80))
+            .build()];
+        bodies.extend((0..n).map(|i| {
+            let radius = ORBITS_FIRST_RADIUS + ORBITS_SPACING * i as Scalar;
+            // Spread out by the golden angle so neighbouring orbits don't start lined up
+            let angle = i as Scalar * 2.399_963;
+            let direction = Vector2D::new(angle.cos(), angle.sin());
+            let speed = (grav_const * ORBITS_CENTRAL_MASS / radius).sqrt();
+            PhysicsBody::builder()
+                .pos(PRESET_CENTER + direction * radius)
+                .mass(ORBITS_BODY_MASS)
+                .velocity(Vector2D::new(-direction.y, direction.x) * speed)
+                .color(
+                    graphics::Color::new(90, 140, 255)
+                        .lerp(graphics::Color::new(90, 255, 160), i as f32 / n.max(2) as f32),
+                )
+                .build()
+        }));
+
+        Simulation::new(bodies, Some(grav_const), None, None, CollisionMode::None)
+    }
+
+    pub fn from_state(state: SimulationState) -> Simulation {
+        let mut simulation = Simulation::new(
+            state.bodies,
+            Some(state.grav_const),
+            Some(state.physics_speed),
+            None,
+            state.collision_mode,
+        );
+        simulation.set_collision_visuals(state.collision_visuals);
+        simulation.set_gravity_matrix(state.gravity_matrix);
+        simulation
+    }
+
+    pub fn load_from_path(path: impl AsRef<Path>) -> io::Result<Simulation> {
+        SimulationState::load(path).map(Simulation::from_state)
+    }
+
+    // Immutable access
+    pub fn bodies(&self) -> &Vec<PhysicsBody> {
+        &self.bodies
+    }
+
+    pub fn grav_const(&self) -> &Scalar {
         &self.grav_const
     }
 
@@ -73,29 +664,483 @@ impl Simulation {
         &self.physics_speed
     }
 
+    pub fn dt(&self) -> &Scalar {
+        &self.dt
+    }
+
+    pub fn num_of_bodies(&self) -> &usize {
+        &self.num_of_bodies
+    }
+
+    pub fn seed(&self) -> &Option<u64> {
+        &self.seed
+    }
+
+    pub fn time_scale(&self) -> &Scalar {
+        &self.time_scale
+    }
+
     pub fn selected_body(&self) -> &Option<usize> {
         &self.selected_body
     }
 
+    pub fn collision_mode(&self) -> &CollisionMode {
+        &self.collision_mode
+    }
+
+    pub fn gravity_mode(&self) -> &GravityMode {
+        &self.gravity_mode
+    }
+
+    pub fn integrator(&self) -> &Integrator {
+        &self.integrator
+    }
+
+    pub fn max_trail_length(&self) -> &Option<usize> {
+        &self.max_trail_length
+    }
+
+    pub fn show_prediction_cone(&self) -> &bool {
+        &self.show_prediction_cone
+    }
+
+    pub fn prediction_mode(&self) -> &PredictionMode {
+        &self.prediction_mode
+    }
+
+    pub fn show_center_of_mass(&self) -> &bool {
+        &self.show_center_of_mass
+    }
+
+    pub fn show_force_arrows(&self) -> &bool {
+        &self.show_force_arrows
+    }
+
+    pub fn prediction_perturbation(&self) -> &Scalar {
+        &self.prediction_perturbation
+    }
+
+    pub fn trail_blend_mode(&self) -> &graphics::BlendMode {
+        &self.trail_blend_mode
+    }
+
+    pub fn trail_fade(&self) -> &bool {
+        &self.trail_fade
+    }
+
+    pub fn trail_style(&self) -> &TrailStyle {
+        &self.trail_style
+    }
+
+    pub fn color_mode(&self) -> &ColorMode {
+        &self.color_mode
+    }
+
+    pub fn integration_substeps(&self) -> &u32 {
+        &self.integration_substeps
+    }
+
+    pub fn theme(&self) -> &graphics::Theme {
+        &self.theme
+    }
+
+    pub fn pending_body(&self) -> &Option<PhysicsBody> {
+        &self.pending_body
+    }
+
+    pub fn restitution(&self) -> &Scalar {
+        &self.restitution
+    }
+
+    pub fn damping(&self) -> &Scalar {
+        &self.damping
+    }
+
+    pub fn softening(&self) -> &Scalar {
+        &self.softening
+    }
+
+    pub fn bounds(&self) -> &Option<Bounds> {
+        &self.bounds
+    }
+
+    pub fn boundary_mode(&self) -> &BoundaryMode {
+        &self.boundary_mode
+    }
+
+    pub fn wall_restitution(&self) -> &Scalar {
+        &self.wall_restitution
+    }
+
+    pub fn dragged_body(&self) -> Option<usize> {
+        self.dragged_body.map(|(i, _)| i)
+    }
+
+    pub fn freeze_dragged_body(&self) -> &bool {
+        &self.freeze_dragged_body
+    }
+
+    pub fn csv_logger(&self) -> &Option<CsvLogger> {
+        &self.csv_logger
+    }
+
+    pub fn gravity_matrix(&self) -> &GravityMatrix {
+        &self.gravity_matrix
+    }
+
+    pub fn collision_visuals(&self) -> &CollisionVisuals {
+        &self.collision_visuals
+    }
+
     // Mutable access
     pub fn bodies_mut(&mut self) -> &mut Vec<PhysicsBody> {
         &mut self.bodies
     }
 
+    pub fn gravity_matrix_mut(&mut self) -> &mut GravityMatrix {
+        &mut self.gravity_matrix
+    }
+
     // Setters
-    pub fn set_grav_const(&mut self, val: f32) {
+    pub fn set_grav_const(&mut self, val: Scalar) {
         self.grav_const = val
     }
 
+    // 0 pauses the simulation, physics_tick then does nothing
     pub fn set_physics_speed(&mut self, val: u32) {
-        self.physics_speed = val.clamp(1, 16)
+        self.physics_speed = val.min(16)
+    }
+
+    pub fn set_dt(&mut self, val: Scalar) {
+        self.dt = val.abs()
+    }
+
+    pub fn set_num_of_bodies(&mut self, val: usize) {
+        self.num_of_bodies = val
+    }
+
+    pub fn set_seed(&mut self, val: Option<u64>) {
+        self.seed = val
+    }
+
+    pub fn set_time_scale(&mut self, val: Scalar) {
+        self.time_scale = val.abs().clamp(MIN_TIME_SCALE, MAX_TIME_SCALE)
+    }
+
+    pub fn set_collision_mode(&mut self, val: CollisionMode) {
+        self.collision_mode = val
+    }
+
+    pub fn set_gravity_mode(&mut self, val: GravityMode) {
+        self.gravity_mode = match val {
+            GravityMode::BarnesHut { theta } => GravityMode::BarnesHut { theta: theta.abs() },
+            GravityMode::Direct => GravityMode::Direct,
+        }
+    }
+
+    pub fn set_integrator(&mut self, val: Integrator) {
+        self.integrator = val
+    }
+
+    // None keeps the whole trail, existing trails longer than val lose their oldest points
+    pub fn set_max_trail_length(&mut self, val: Option<usize>) {
+        self.max_trail_length = val;
+        self.bodies.iter_mut().for_each(|x| x.truncate_trail(val))
+    }
+
+    pub fn set_show_prediction_cone(&mut self, val: bool) {
+        self.show_prediction_cone = val
+    }
+
+    pub fn set_prediction_mode(&mut self, val: PredictionMode) {
+        self.prediction_mode = val
+    }
+
+    pub fn set_show_center_of_mass(&mut self, val: bool) {
+        self.show_center_of_mass = val
+    }
+
+    pub fn set_show_force_arrows(&mut self, val: bool) {
+        self.show_force_arrows = val
+    }
+
+    pub fn set_prediction_perturbation(&mut self, val: Scalar) {
+        self.prediction_perturbation = val.abs()
+    }
+
+    pub fn set_trail_blend_mode(&mut self, val: graphics::BlendMode) {
+        self.trail_blend_mode = val
+    }
+
+    pub fn set_trail_fade(&mut self, val: bool) {
+        self.trail_fade = val
+    }
+
+    pub fn set_trail_style(&mut self, val: TrailStyle) {
+        self.trail_style = val
+    }
+
+    pub fn set_color_mode(&mut self, val: ColorMode) {
+        self.color_mode = val
+    }
+
+    pub fn set_integration_substeps(&mut self, val: u32) {
+        self.integration_substeps = val.max(1)
+    }
+
+    pub fn set_theme(&mut self, val: graphics::Theme) {
+        self.theme = val
+    }
+
+    // 1.0 is perfectly elastic, 0.0 removes all relative velocity along the collision normal
+    pub fn set_restitution(&mut self, val: Scalar) {
+        self.restitution = val.clamp(0.0, 1.0)
+    }
+
+    // 0.0 keeps all momentum, 1.0 stops every body after a single tick
+    pub fn set_damping(&mut self, val: Scalar) {
+        self.damping = val.clamp(0.0, 1.0)
+    }
+
+    // Plummer softening length, gravity between bodies closer than this is smoothed out instead of blowing up
+    pub fn set_softening(&mut self, val: Scalar) {
+        self.softening = val.abs()
+    }
+
+    pub fn set_bounds(&mut self, val: Option<Bounds>) {
+        self.bounds = val
+    }
+
+    pub fn set_boundary_mode(&mut self, val: BoundaryMode) {
+        self.boundary_mode = val
+    }
+
+    // Fraction of the velocity towards a wall that is kept when bouncing off it
+    pub fn set_wall_restitution(&mut self, val: Scalar) {
+        self.wall_restitution = val.clamp(0.0, 1.0)
+    }
+
+    pub fn set_freeze_dragged_body(&mut self, val: bool) {
+        self.freeze_dragged_body = val
+    }
+
+    pub fn set_csv_logger(&mut self, val: Option<CsvLogger>) {
+        self.csv_logger = val
+    }
+
+    pub fn set_gravity_matrix(&mut self, val: GravityMatrix) {
+        self.gravity_matrix = val
+    }
+
+    pub fn set_collision_visuals(&mut self, val: CollisionVisuals) {
+        self.collision_visuals = val
+    }
+
+    // Replaces the bodies and the saved settings, everything else is kept
+    pub fn set_state(&mut self, state: SimulationState) {
+        self.bodies = state.bodies;
+        self.selected_body = None;
+        self.dragged_body = None;
+        self.clear_history();
+        self.set_grav_const(state.grav_const);
+        self.set_physics_speed(state.physics_speed);
+        self.set_collision_mode(state.collision_mode);
+        self.set_gravity_matrix(state.gravity_matrix);
+        self.set_collision_visuals(state.collision_visuals);
+        self.flashes.clear();
     }
 
     // Methods
+    pub fn state(&self, include_trails: bool) -> SimulationState {
+        SimulationState {
+            bodies: self
+                .bodies
+                .iter()
+                .map(|x| {
+                    let mut body = x.clone();
+                    if !include_trails {
+                        body.trail.clear();
+                    }
+                    body
+                })
+                .collect(),
+            grav_const: self.grav_const,
+            physics_speed: self.physics_speed,
+            collision_mode: self.collision_mode,
+            gravity_matrix: self.gravity_matrix.clone(),
+            collision_visuals: self.collision_visuals,
+        }
+    }
+
+    // Presets bring their own bodies and constants but keep how collisions look
+    fn load_preset(&mut self, preset: Simulation) {
+        self.set_state(SimulationState {
+            collision_visuals: self.collision_visuals,
+            ..preset.state(false)
+        })
+    }
+
+    pub fn save_to_path(&self, path: impl AsRef<Path>, include_trails: bool) -> io::Result<()> {
+        self.state(include_trails).save(path)
+    }
+
+    // Only forgets the recorded history, bodies keep moving as before
+    pub fn clear_trails(&mut self) {
+        self.bodies.iter_mut().for_each(|x| x.trail.clear())
+    }
+
+    // Advances the simulation without any rendering and returns the bodies at the end
+    pub fn run_headless(&mut self, steps: usize) -> Vec<PhysicsBody> {
+        (0..steps).for_each(|_| {
+            self.physics_tick();
+        });
+        self.bodies.clone()
+    }
+
     pub fn shapes(&self) -> Vec<Box<dyn graphics::Draw>> {
+        self.shapes_where(|_| true)
+    }
+
+    // Like shapes, but bodies whose shapes all lie outside the rectangle from min to max are left out
+    pub fn shapes_within(&self, min: Vector2D<Scalar>, max: Vector2D<Scalar>) -> Vec<Box<dyn graphics::Draw>> {
+        self.shapes_where(|x| {
+            let (body_min, body_max) = x.bounding_box();
+            body_min.x <= max.x && body_max.x >= min.x && body_min.y <= max.y && body_max.y >= min.y
+        })
+    }
+
+    fn shapes_where(&self, draw_body: impl Fn(&PhysicsBody) -> bool) -> Vec<Box<dyn graphics::Draw>> {
+        let mut out: Vec<Box<dyn graphics::Draw>> = vec![];
+        // Recomputed every frame since absorbing changes the masses, over all bodies so culling doesn't change colors
+        let max_speed = self.bodies.iter().map(|x| x.velocity.speed()).fold(0.0, Scalar::max);
+        let max_mass = self.bodies.iter().map(|x| x.mass).fold(0.0, Scalar::max);
+        for i in self.bodies.iter().filter(|x| draw_body(x)) {
+            let color = match self.color_mode {
+                ColorMode::Fixed => i.color,
+                ColorMode::Speed if max_speed > 0.0 => {
+                    SLOW_COLOR.lerp(FAST_COLOR, to_f32(i.velocity.speed() / max_speed))
+                }
+                ColorMode::Speed => SLOW_COLOR,
+                ColorMode::Mass if max_mass > 0.0 => LIGHT_COLOR.lerp(HEAVY_COLOR, to_f32(i.mass / max_mass)),
+                ColorMode::Mass => LIGHT_COLOR,
+                ColorMode::Group => GROUP_COLORS[i.group as usize % GROUP_COLORS.len()],
+            };
+            i.shape_with_color(
+                &self.theme,
+                self.trail_blend_mode,
+                self.trail_fade,
+                self.trail_style,
+                color,
+            )
+            .into_iter()
+            .for_each(|x| out.push(x))
+        }
+        if self.show_force_arrows {
+            for (body, force) in self.bodies.iter().zip(self.net_forces()) {
+                if force.length() > 0.0 {
+                    let length = (force.length() * FORCE_ARROW_SCALE).min(MAX_FORCE_ARROW_LENGTH);
+                    out.push(Box::new(graphics::Line::new(
+                        to_f32_vec(body.pos),
+                        to_f32_vec(body.pos + force.normalise() * length),
+                        2,
+                        self.theme.force_arrow,
+                    )));
+                }
+            }
+        }
+        if let Some(bounds) = self.bounds {
+            if self.boundary_mode != BoundaryMode::None {
+                out.append(&mut bounds.shapes(self.theme.grid));
+            }
+        }
+        if self.show_center_of_mass && !self.bodies.is_empty() {
+            let center_of_mass = self.center_of_mass();
+            let (horizontal, vertical) = (
+                Vector2D::new(CENTER_OF_MASS_MARKER_SIZE, 0.0),
+                Vector2D::new(0.0, CENTER_OF_MASS_MARKER_SIZE),
+            );
+            out.push(Box::new(graphics::Line::new(
+                to_f32_vec(center_of_mass - horizontal),
+                to_f32_vec(center_of_mass + horizontal),
+                2,
+                self.theme.hud,
+            )));
+            out.push(Box::new(graphics::Line::new(
+                to_f32_vec(center_of_mass - vertical),
+                to_f32_vec(center_of_mass + vertical),
+                2,
+                self.theme.hud,
+            )));
+        }
+        if self.collision_visuals.flash_intensity > 0.0 {
+            for flash in &self.flashes {
+                let fade = 1.0 - flash.age as f32 / FLASH_TICKS as f32;
+                let color = self.theme.background.lerp(
+                    self.collision_visuals.flash_color,
+                    self.collision_visuals.flash_intensity * fade,
+                );
+                out.push(Box::new(graphics::Circle::new(
+                    to_f32_vec(flash.pos),
+                    to_f32(flash.radius),
+                    0,
+                    color,
+                )));
+            }
+        }
+        if let Some(pending_body) = &self.pending_body {
+            out.append(&mut pending_body.shape(&self.theme, self.trail_blend_mode));
+        }
+        if let Some(selected_body) = self.selected_body {
+            if let Some(body) = self.get_body(selected_body) {
+                let mut ring = graphics::Circle::new(
+                    to_f32_vec(body.pos),
+                    to_f32(body.radius + SELECTION_RING_GAP),
+                    3,
+                    self.theme.hud,
+                );
+                ring.set_filled(false);
+                out.push(Box::new(ring));
+            }
+            out.append(&mut self.prediction_shapes(selected_body));
+            if self.show_prediction_cone {
+                out.append(&mut self.prediction_cone_shapes(selected_body));
+            }
+        }
+        out
+    }
+
+    // Dashed line along the predicted path of a body
+    pub fn prediction_shapes(&self, index: usize) -> Vec<Box<dyn graphics::Draw>> {
         let mut out: Vec<Box<dyn graphics::Draw>> = vec![];
-        for i in &self.bodies {
-            i.shape().into_iter().for_each(|x| out.push(x))
+        if let Some(body) = self.get_body(index) {
+            let color = graphics::Color::new(body.color.r / 2, body.color.g / 2, body.color.b / 2);
+            let path = self.predict_trajectory(index, PREDICTION_STEPS, self.prediction_mode);
+            for i in (1..path.len()).step_by(2) {
+                out.push(Box::new(graphics::Line::new(
+                    to_f32_vec(path[i - 1]),
+                    to_f32_vec(path[i]),
+                    0,
+                    color,
+                )))
+            }
+        }
+        out
+    }
+
+    // Translucent, so the cone is brightest where most of the paths still agree
+    pub fn prediction_cone_shapes(&self, index: usize) -> Vec<Box<dyn graphics::Draw>> {
+        let mut out: Vec<Box<dyn graphics::Draw>> = vec![];
+        if let Some(body) = self.get_body(index) {
+            for path in self.predict_ensemble(
+                index,
+                PREDICTION_STEPS,
+                self.prediction_perturbation,
+                PREDICTION_ENSEMBLE_SIZE,
+            ) {
+                let mut line = graphics::Polyline::new(path.iter().map(|x| to_f32_vec(*x)).collect(), 0, body.color);
+                line.set_blend_mode(graphics::BlendMode::Alpha(PREDICTION_CONE_ALPHA));
+                out.push(Box::new(line))
+            }
         }
         out
     }
@@ -109,112 +1154,865 @@ impl Simulation {
     }
 
     pub fn remove_body(&mut self, i: usize) {
+        if let Some(body) = self.take_body(i) {
+            self.record(vec![Edit::Removed(i, body)])
+        }
+    }
+
+    pub fn add_body(&mut self, physics_body: PhysicsBody) {
+        self.bodies.push(physics_body);
+        self.record(vec![Edit::Added(self.bodies.len() - 1)]);
+    }
+
+    // Only adding, removing, merging and shattering bodies can be undone. Bodies keep the motion they had since, an
+    // undone removal puts the body back how it was when removed. Nothing happens once the history is empty
+    pub fn undo(&mut self) {
+        if let Some(edits) = self.undo_history.pop_back() {
+            let reverted = self.revert(edits);
+            self.redo_history.push(reverted)
+        }
+    }
+
+    pub fn redo(&mut self) {
+        if let Some(edits) = self.redo_history.pop() {
+            let reverted = self.revert(edits);
+            self.undo_history.push_back(reverted)
+        }
+    }
+
+    // Edits made directly through bodies_mut aren't recorded, so the history has to be cleared after them
+    pub fn clear_history(&mut self) {
+        self.undo_history.clear();
+        self.redo_history.clear();
+    }
+
+    fn record(&mut self, edits: Vec<Edit>) {
+        self.redo_history.clear();
+        self.undo_history.push_back(edits);
+        if self.undo_history.len() > MAX_UNDO_HISTORY {
+            self.undo_history.pop_front();
+        }
+    }
+
+    // Reverts the edits from last to first and returns the edits that revert that again
+    fn revert(&mut self, edits: Vec<Edit>) -> Vec<Edit> {
+        edits
+            .into_iter()
+            .rev()
+            .filter_map(|edit| match edit {
+                Edit::Added(i) => self.take_body(i).map(|body| Edit::Removed(i, body)),
+                Edit::Removed(i, body) => {
+                    let i = i.min(self.bodies.len());
+                    self.bodies.insert(i, body);
+                    if let Some(selected_body) = self.selected_body {
+                        if i <= selected_body {
+                            self.selected_body = Some(selected_body + 1)
+                        }
+                    }
+                    if let Some((dragged_body, was_fixed)) = self.dragged_body {
+                        if i <= dragged_body {
+                            self.dragged_body = Some((dragged_body + 1, was_fixed))
+                        }
+                    }
+                    Some(Edit::Added(i))
+                }
+                Edit::Changed(i, body) => self
+                    .bodies
+                    .get_mut(i)
+                    .map(|x| Edit::Changed(i, std::mem::replace(x, body))),
+            })
+            .collect()
+    }
+
+    // Removes a body without recording it, keeping the selection on the same body
+    fn take_body(&mut self, i: usize) -> Option<PhysicsBody> {
         if i < self.bodies.len() {
-            self.bodies.remove(i);
+            let body = self.bodies.remove(i);
 
             if let Some(selected_body) = self.selected_body {
                 if selected_body == i {
                     self.selected_body = None
-                } else if i > selected_body {
+                } else if i < selected_body {
                     self.selected_body = Some(selected_body - 1)
                 }
             }
+            if let Some((dragged_body, was_fixed)) = self.dragged_body {
+                if dragged_body == i {
+                    self.dragged_body = None
+                } else if i < dragged_body {
+                    self.dragged_body = Some((dragged_body - 1, was_fixed))
+                }
+            }
+            Some(body)
+        } else {
+            None
+        }
+    }
+
+    pub fn attracts(&self, body1: &PhysicsBody, body2: &PhysicsBody) -> bool {
+        self.gravity_matrix.interacts(body1.group, body2.group)
+    }
+
+    pub fn gravity_between(&self, body1: &PhysicsBody, body2: &PhysicsBody) -> Force {
+        Force::from_vector2d(self.point_gravity(*body1.pos(), *body1.mass(), *body2.pos(), *body2.mass()))
+    }
+
+    fn wraps(&self) -> bool {
+        self.bounds.is_some() && self.boundary_mode == BoundaryMode::Wrap
+    }
+
+    // pos1 - pos2, across the bounds when they wrap around
+    pub fn displacement(&self, pos1: Vector2D<Scalar>, pos2: Vector2D<Scalar>) -> Vector2D<Scalar> {
+        match (self.bounds, self.boundary_mode) {
+            (Some(bounds), BoundaryMode::Wrap) => bounds.minimum_image(pos1 - pos2),
+            _ => pos1 - pos2,
+        }
+    }
+
+    // Force on a point mass at pos1 from one at pos2, pointing away from pos2 like gravity_between
+    fn point_gravity(
+        &self,
+        pos1: Vector2D<Scalar>,
+        mass1: Scalar,
+        pos2: Vector2D<Scalar>,
+        mass2: Scalar,
+    ) -> Vector2D<Scalar> {
+        let displacement = self.displacement(pos1, pos2);
+        let softened_dist_sq = displacement.length_squared() + self.softening.powf(2.0);
+        if softened_dist_sq > 0.0 {
+            displacement * (self.grav_const * mass1 * mass2) / softened_dist_sq.powf(1.5)
+        } else {
+            Vector2D::new(0.0, 0.0)
+        }
+    }
+
+    pub fn total_kinetic_energy(&self) -> Scalar {
+        self.bodies
+            .iter()
+            .map(|x| 0.5 * x.mass * x.velocity.0.length_squared())
+            .sum()
+    }
+
+    // Summed over unique pairs, softened the same way as the gravity so it is conserved with it
+    pub fn total_potential_energy(&self) -> Scalar {
+        (0..self.bodies.len())
+            .tuple_combinations()
+            .filter(|(i, j)| self.attracts(&self.bodies[*i], &self.bodies[*j]))
+            .map(|(i, j)| {
+                let (body1, body2) = (&self.bodies[i], &self.bodies[j]);
+                let dist_sq = self.displacement(body1.pos, body2.pos).length_squared();
+                let softened_dist = (dist_sq + self.softening.powf(2.0)).sqrt();
+                if softened_dist > 0.0 {
+                    -self.grav_const * body1.mass * body2.mass / softened_dist
+                } else {
+                    0.0
+                }
+            })
+            .sum()
+    }
+
+    pub fn total_energy(&self) -> Scalar {
+        self.total_kinetic_energy() + self.total_potential_energy()
+    }
+
+    // Corners of the smallest rectangle holding every body, the smallest coordinates first and None without bodies.
+    // Unlike bounds, which keeps bodies inside of it, this follows wherever the bodies are
+    pub fn extent(&self) -> Option<(Vector2D<Scalar>, Vector2D<Scalar>)> {
+        self.bodies
+            .iter()
+            .map(|x| {
+                let radius = Vector2D::new(x.radius, x.radius);
+                (x.pos - radius, x.pos + radius)
+            })
+            .reduce(|(min, max), (body_min, body_max)| {
+                (
+                    Vector2D::new(min.x.min(body_min.x), min.y.min(body_min.y)),
+                    Vector2D::new(max.x.max(body_max.x), max.y.max(body_max.y)),
+                )
+            })
+    }
+
+    // Mass-weighted average position, the origin if there is no mass
+    pub fn center_of_mass(&self) -> Vector2D<Scalar> {
+        let total_mass: Scalar = self.bodies.iter().map(|x| x.mass).sum();
+        if total_mass > 0.0 {
+            self.bodies
+                .iter()
+                .fold(Vector2D::new(0.0, 0.0), |acc, x| acc + x.pos * x.mass)
+                / total_mass
+        } else {
+            Vector2D::new(0.0, 0.0)
+        }
+    }
+
+    pub fn total_momentum(&self) -> Vector2D<Scalar> {
+        self.bodies
+            .iter()
+            .fold(Vector2D::new(0.0, 0.0), |acc, x| acc + x.velocity.0 * x.mass)
+    }
+
+    // min_radius lets bodies that are drawn larger than their radius be picked by their drawn size
+    pub fn get_bodies_on_point(&self, p: Vector2D<Scalar>, min_radius: Scalar) -> Vec<&PhysicsBody> {
+        self.bodies
+            .iter()
+            .filter(|x| ((x.pos.x - p.x).powf(2.0) + (x.pos.y - p.y).powf(2.0)) < x.radius.max(min_radius).powf(2.0))
+            .collect()
+    }
+
+    pub fn get_body_on_point_index(&self, p: Vector2D<Scalar>, min_radius: Scalar) -> Option<usize> {
+        self.bodies
+            .iter()
+            .position(|x| ((x.pos.x - p.x).powf(2.0) + (x.pos.y - p.y).powf(2.0)) < x.radius.max(min_radius).powf(2.0))
+    }
+
+    // Fixed bodies stay still, their kicks are still drawn so a seed kicks the other bodies the same either way
+    pub fn perturb(&mut self, magnitude: Scalar, rng: &mut impl Rng) {
+        self.bodies
+            .iter_mut()
+            .map(|x| {
+                let kick = Vector2D::new(rng.gen::<Scalar>() - 0.5, rng.gen::<Scalar>() - 0.5).normalise()
+                    * (rng.gen::<Scalar>() * magnitude);
+                (x, kick)
+            })
+            .filter(|(x, _)| !x.fixed)
+            .for_each(|(x, kick)| x.velocity += Velocity(kick))
+    }
+
+    pub fn closest_pair(&self) -> Option<(usize, usize, Scalar)> {
+        (0..self.bodies.len())
+            .tuple_combinations()
+            .map(|(i, j)| (i, j, self.bodies[i].distance_between(&self.bodies[j])))
+            .min_by(|a, b| a.2.total_cmp(&b.2))
+    }
+
+    // Prediction
+
+    // Copy of the simulation used for look-ahead, without trails or collisions
+    fn prediction_copy(&self) -> Simulation {
+        let mut out = Simulation::new(
+            self.bodies
+                .iter()
+                .map(|x| {
+                    let mut body = PhysicsBody::new(x.pos, x.mass, x.velocity, x.color);
+                    body.set_fixed(x.fixed);
+                    body.set_group(x.group);
+                    body
+                })
+                .collect(),
+            Some(self.grav_const),
+            Some(1),
+            Some(self.dt),
+            CollisionMode::None,
+        );
+        out.set_max_trail_length(Some(0));
+        out.set_integration_substeps(self.integration_substeps);
+        out.set_gravity_mode(self.gravity_mode);
+        out.set_integrator(self.integrator);
+        out.set_softening(self.softening);
+        out.set_bounds(self.bounds);
+        out.set_boundary_mode(self.boundary_mode);
+        out.set_wall_restitution(self.wall_restitution);
+        out.set_gravity_matrix(self.gravity_matrix.clone());
+        out
+    }
+
+    // Positions of a body over the next steps ticks, the simulation itself is left untouched
+    pub fn predict_trajectory(&self, index: usize, steps: usize, mode: PredictionMode) -> Vec<Vector2D<Scalar>> {
+        if index >= self.bodies.len() {
+            return vec![];
+        }
+        match mode {
+            PredictionMode::FullNBody => {
+                let mut prediction = self.prediction_copy();
+                (0..steps)
+                    .map(|_| {
+                        prediction.physics_tick();
+                        prediction.bodies[index].pos
+                    })
+                    .collect()
+            }
+            PredictionMode::FrozenField if self.bodies[index].fixed => vec![self.bodies[index].pos; steps],
+            PredictionMode::FrozenField => {
+                let body = &self.bodies[index];
+                let mut body = PhysicsBody::new(body.pos, body.mass, body.velocity, body.color);
+                let dt = self.dt / self.integration_substeps as Scalar;
+                (0..steps)
+                    .map(|_| {
+                        for _ in 0..self.integration_substeps {
+                            match self.integrator {
+                                Integrator::Euler => {
+                                    body.accelerate(self.frozen_field_at(index, body.pos), dt);
+                                    body.move_self(dt);
+                                }
+                                Integrator::VelocityVerlet => {
+                                    if body.acceleration.is_none() {
+                                        body.acceleration = Some(self.frozen_field_at(index, body.pos));
+                                    }
+                                    body.move_self_verlet(dt);
+                                    body.accelerate_verlet(self.frozen_field_at(index, body.pos), dt);
+                                }
+                            }
+                            if let Some(bounds) = self.bounds {
+                                body.confine(&bounds, self.boundary_mode, self.wall_restitution);
+                            }
+                        }
+                        body.pos
+                    })
+                    .collect()
+            }
+        }
+    }
+
+    // Gravitational acceleration a test mass at point would feel from all bodies
+    pub fn sample_field(&self, point: Vector2D<Scalar>) -> Vector2D<Scalar> {
+        self.bodies.iter().fold(Vector2D::new(0.0, 0.0), |acc, x| {
+            acc - self.point_gravity(point, 1.0, x.pos, x.mass)
+        })
+    }
+
+    // An arrow along the field at every point, lengths grow with the strength but never past max_length
+    pub fn field_shapes(&self, points: &[Vector2D<f32>], max_length: f32) -> Vec<Box<dyn graphics::Draw>> {
+        let color = self.theme.grid.lerp(self.theme.arrow, 0.3);
+        points
+            .iter()
+            .filter_map(|point| {
+                let field = self.sample_field(from_f32_vec(*point));
+                let strength = field.length();
+                if strength > 0.0 && strength.is_finite() {
+                    let length = max_length * to_f32(strength / (strength + FIELD_ARROW_HALF_ACCELERATION));
+                    let arrow: Box<dyn graphics::Draw> = Box::new(graphics::Line::new(
+                        *point,
+                        *point + to_f32_vec(field.normalise()) * length,
+                        0,
+                        color,
+                    ));
+                    Some(arrow)
+                } else {
+                    None
+                }
+            })
+            .collect()
+    }
+
+    // Acceleration at pos from every body except the one at index
+    fn frozen_field_at(&self, index: usize, pos: Vector2D<Scalar>) -> Vector2D<Scalar> {
+        self.bodies
+            .iter()
+            .enumerate()
+            .filter(|(i, x)| *i != index && self.attracts(&self.bodies[index], x))
+            .fold(Vector2D::new(0.0, 0.0), |acc, (_, x)| {
+                acc - self.point_gravity(pos, 1.0, x.pos, x.mass)
+            })
+    }
+
+    pub fn predict_ensemble(
+        &self,
+        index: usize,
+        steps: usize,
+        perturbation: Scalar,
+        count: usize,
+    ) -> Vec<Vec<Vector2D<Scalar>>> {
+        if index >= self.bodies.len() {
+            return vec![];
+        }
+        let mut rng = rand::thread_rng();
+        (0..count.min(MAX_PREDICTION_ENSEMBLE_SIZE))
+            .map(|_| {
+                let mut perturbed = self.prediction_copy();
+                let kick = Vector2D::new(rng.gen::<Scalar>() - 0.5, rng.gen::<Scalar>() - 0.5).normalise()
+                    * (rng.gen::<Scalar>() * perturbation);
+                perturbed.bodies[index].velocity += Velocity(kick);
+                perturbed.predict_trajectory(index, steps, self.prediction_mode)
+            })
+            .collect()
+    }
+
+    // Physics
+
+    // Returns every collision during the ticks, see physics_tick_dt
+    pub fn physics_tick(&mut self) -> Vec<CollisionEvent> {
+        self.physics_tick_dt(self.dt * self.time_scale)
+    }
+
+    // dt is measured in ticks: a body moves by its velocity (world units per tick) times dt and gravity adds
+    // force / mass times dt to the velocity. physics_speed is the number of ticks per frame, each tick is
+    // integrated in integration_substeps steps. physics_tick scales dt by time_scale, so slowing down keeps
+    // the same number of ticks per frame with each covering less time
+    pub fn physics_tick_dt(&mut self, dt: Scalar) -> Vec<CollisionEvent> {
+        let dt = dt / self.integration_substeps as Scalar;
+        let mut collisions = vec![];
+        // Collisions before this one already have a flash
+        let mut flashed = 0;
+        for _ in 0..self.physics_speed {
+            for _ in 0..self.integration_substeps {
+                match self.integrator {
+                    Integrator::Euler => {
+                        self.gravity_tick(dt);
+                        self.movement_tick(dt);
+                    }
+                    Integrator::VelocityVerlet => {
+                        // A gravity tick of length 0 only records the accelerations of new bodies
+                        if self.bodies.iter().any(|x| x.accelerated() && x.acceleration.is_none()) {
+                            self.gravity_tick(0.0);
+                        }
+                        self.movement_tick(dt);
+                        self.gravity_tick(dt);
+                    }
+                }
+                collisions.append(&mut self.collision_tick());
+            }
+            self.flash_tick(&collisions[flashed..]);
+            flashed = collisions.len();
+            self.trail_tick();
+            self.log_tick();
         }
+        collisions
+    }
+
+    // Ages the flashes by a tick and starts one for every new collision, the oldest go first once there are too many
+    fn flash_tick(&mut self, collisions: &[CollisionEvent]) {
+        self.flashes.iter_mut().for_each(|x| x.age += 1);
+        self.flashes.retain(|x| x.age < FLASH_TICKS);
+        self.flashes.extend(collisions.iter().map(|x| Flash {
+            pos: x.pos,
+            radius: PhysicsBody::radius_from_mass(x.mass) * FLASH_SIZE,
+            age: 0,
+        }));
+        let excess = self.flashes.len().saturating_sub(MAX_FLASHES);
+        self.flashes.drain(..excess);
     }
 
-    pub fn add_body(&mut self, physics_body: PhysicsBody) {
-        self.bodies.push(physics_body);
+    pub fn movement_tick(&mut self, dt: Scalar) {
+        if self.damping > 0.0 {
+            // Raised to dt so that the loss per tick doesn't depend on the number of substeps
+            let keep = (1.0 - self.damping).powf(dt);
+            self.bodies
+                .iter_mut()
+                .for_each(|x| x.velocity = Velocity(x.velocity.0 * keep));
+        }
+        let moving = self.bodies.iter_mut().filter(|x| !x.fixed);
+        match self.integrator {
+            Integrator::Euler => moving.for_each(|x| x.move_self(dt)),
+            Integrator::VelocityVerlet => moving.for_each(|x| x.move_self_verlet(dt)),
+        }
+        if let Some(bounds) = self.bounds {
+            let (boundary_mode, wall_restitution) = (self.boundary_mode, self.wall_restitution);
+            self.bodies
+                .iter_mut()
+                .filter(|x| !x.fixed)
+                .for_each(|x| x.confine(&bounds, boundary_mode, wall_restitution));
+        }
+    }
+
+    pub fn trail_tick(&mut self) {
+        let max_trail_length = self.max_trail_length;
+        self.bodies.iter_mut().for_each(|x| x.add_trail(max_trail_length))
+    }
+
+    // Logging stops at the first error instead of reporting it every tick
+    pub fn log_tick(&mut self) {
+        if let Some(csv_logger) = &mut self.csv_logger {
+            if let Err(e) = csv_logger.log(&self.bodies) {
+                eprintln!("Unable to write to {}: {e}", csv_logger.path().display());
+                self.csv_logger = None;
+            }
+        }
+    }
+
+    pub fn gravity_tick(&mut self, dt: Scalar) {
+        let mut forces = std::mem::take(&mut self.force_buffer);
+        forces.clear();
+        forces.resize(self.bodies.len(), Vector2D::new(0.0, 0.0));
+        match self.gravity_mode {
+            GravityMode::BarnesHut { theta } if !self.wraps() => self.barnes_hut_forces_into(theta, &mut forces),
+            _ => self.direct_forces_into(&mut forces),
+        };
+        let integrator = self.integrator;
+        self.bodies.iter_mut().zip(&forces).for_each(|(x, force)| {
+            if x.accelerated() {
+                match integrator {
+                    Integrator::Euler => x.accelerate(*force / x.mass, dt),
+                    Integrator::VelocityVerlet => x.accelerate_verlet(*force / x.mass, dt),
+                }
+            }
+        });
+        self.force_buffer = forces;
+    }
+
+    // Gravitational force on every body at its current position, computed the same way as in gravity_tick. The tree
+    // can't measure distances across bounds that wrap around, so those always use the direct sum
+    pub fn net_forces(&self) -> Vec<Vector2D<Scalar>> {
+        match self.gravity_mode {
+            GravityMode::BarnesHut { theta } if !self.wraps() => self.barnes_hut_forces(theta),
+            _ => self.direct_forces(),
+        }
+    }
+
+    pub fn direct_forces(&self) -> Vec<Vector2D<Scalar>> {
+        let mut forces = vec![Vector2D::new(0.0, 0.0); self.bodies.len()];
+        self.direct_forces_into(&mut forces);
+        forces
+    }
+
+    pub fn barnes_hut_forces(&self, theta: Scalar) -> Vec<Vector2D<Scalar>> {
+        let mut forces = vec![Vector2D::new(0.0, 0.0); self.bodies.len()];
+        self.barnes_hut_forces_into(theta, &mut forces);
+        forces
+    }
+
+    // Each pair is computed once and applied to both bodies in opposite directions, forces must be zeroed and
+    // have one entry per body
+    #[cfg(not(feature = "parallel"))]
+    fn direct_forces_into(&self, forces: &mut [Vector2D<Scalar>]) {
+        (0..self.bodies.len())
+            .tuple_combinations()
+            .filter(|(i, j)| self.attracts(&self.bodies[*i], &self.bodies[*j]))
+            .for_each(|(i, j)| {
+                let grav_force = self.gravity_between(&self.bodies[i], &self.bodies[j]).as_vector2d();
+                forces[i] -= grav_force;
+                forces[j] += grav_force;
+            });
+    }
+
+    // Each thread accumulates the pairs (i, j > i) of its share of bodies into its own buffer, the buffers are
+    // summed afterwards so every pair is still only computed once
+    #[cfg(feature = "parallel")]
+    fn direct_forces_into(&self, forces: &mut [Vector2D<Scalar>]) {
+        let len = self.bodies.len();
+        let summed = (0..len)
+            .into_par_iter()
+            .fold(
+                || vec![Vector2D::new(0.0, 0.0); len],
+                |mut forces, i| {
+                    for j in ((i + 1)..len).filter(|j| self.attracts(&self.bodies[i], &self.bodies[*j])) {
+                        let grav_force = self.gravity_between(&self.bodies[i], &self.bodies[j]).as_vector2d();
+                        forces[i] -= grav_force;
+                        forces[j] += grav_force;
+                    }
+                    forces
+                },
+            )
+            .reduce(
+                || vec![Vector2D::new(0.0, 0.0); len],
+                |mut a, b| {
+                    a.iter_mut().zip(b).for_each(|(x, y)| *x += y);
+                    a
+                },
+            );
+        forces.copy_from_slice(&summed);
+    }
+
+    // The tree doesn't know about wrapping bounds, cells just across an edge are approximated as if they were far,
+    // which is why gravity_tick doesn't use it then. Unless every group attracts every other, each group gets its own
+    // tree where the bodies it isn't attracted by have no mass
+    fn barnes_hut_forces_into(&self, theta: Scalar, forces: &mut [Vector2D<Scalar>]) {
+        let all_interact = self.gravity_matrix.all_interact();
+        let tree_group = |group: u8| if all_interact { 0 } else { group };
+        let trees: HashMap<u8, QuadTree> = self
+            .bodies
+            .iter()
+            .map(|x| tree_group(x.group))
+            .unique()
+            .map(|group| {
+                let points = self
+                    .bodies
+                    .iter()
+                    .map(|x| {
+                        let attracted = all_interact || self.gravity_matrix.interacts(group, x.group);
+                        (x.pos, if attracted { x.mass } else { 0.0 })
+                    })
+                    .collect::<Vec<_>>();
+                (group, QuadTree::new(&points))
+            })
+            .collect();
+        let point_gravity = |pos1, mass1, pos2, mass2| -self.point_gravity(pos1, mass1, pos2, mass2);
+
+        #[cfg(feature = "parallel")]
+        let (bodies, forces) = (self.bodies.par_iter(), forces.par_iter_mut());
+        #[cfg(not(feature = "parallel"))]
+        let (bodies, forces) = (self.bodies.iter(), forces.iter_mut());
+
+        bodies.zip(forces).enumerate().for_each(|(i, (x, force))| {
+            *force = trees[&tree_group(x.group)].force_on(i, x.pos, x.mass, theta, &point_gravity)
+        });
     }
 
-    pub fn gravity_between(&self, body1: &PhysicsBody, body2: &PhysicsBody) -> Force {
-        let dist_between = body1.distance_between(body2);
-        Force::new(
-            Vector2D::new(body1.pos().x - body2.pos().x, body1.pos().y - body2.pos().y).normalise(),
-            (self.grav_const * body1.mass() * body2.mass())
-                / ((if dist_between > 1.0 { dist_between } else { 1.0 }).powf(2.0)),
-        )
+    // Pairs of bodies close enough that they might touch, found through a spatial hash with cells as wide as the
+    // largest body so that only neighbouring cells have to be checked
+    fn collision_candidates(&self) -> Vec<(usize, usize)> {
+        let max_diameter = self.bodies.iter().map(|x| 2.0 * x.radius).fold(0.0, Scalar::max);
+        SpatialHash::new(&self.bodies.iter().map(|x| x.pos).collect::<Vec<_>>(), max_diameter).candidate_pairs()
     }
 
-    pub fn get_bodies_on_point(&self, p: Vector2D<f32>) -> Vec<&PhysicsBody> {
-        self.bodies
-            .iter()
-            .filter(|x| ((x.pos.x - p.x).powf(2.0) + (x.pos.y - p.y).powf(2.0)) < x.radius.powf(2.0))
+    // Every pair of intersecting bodies as (lower index, higher index), in ascending order
+    pub fn colliding_pairs(&self) -> Vec<(usize, usize)> {
+        self.collision_candidates()
+            .into_iter()
+            .filter(|(i, j)| self.bodies[*i].intersects(&self.bodies[*j]))
             .collect()
     }
 
-    pub fn get_body_on_point_index(&self, p: Vector2D<f32>) -> Option<usize> {
-        self.bodies
-            .iter()
-            .position(|x| ((x.pos.x - p.x).powf(2.0) + (x.pos.y - p.y).powf(2.0)) < x.radius.powf(2.0))
+    // Bodies that touch but already move apart, like ones resting against each other, don't count as colliding in
+    // Collide and Shatter
+    pub fn collision_tick(&mut self) -> Vec<CollisionEvent> {
+        let mut collisions = vec![];
+        match self.collision_mode {
+            CollisionMode::None => {}
+            CollisionMode::Collide => self.bounce_colliding(&mut collisions),
+            CollisionMode::Absorb => {
+                // After a merge the survivor has grown, so the pairs are found again until no bodies intersect
+                while let Some((i, j)) = self.colliding_pairs().first().copied() {
+                    collisions.push(self.collision_event(i, j));
+                    let survivor = self.bodies[i].clone();
+                    // The merged body stays under the cursor, fixed afterwards if either part was before
+                    if let Some((dragged_body, was_fixed)) = self.dragged_body {
+                        if dragged_body == i || dragged_body == j {
+                            let other = if dragged_body == i { j } else { i };
+                            self.dragged_body = Some((i, was_fixed || self.bodies[other].fixed));
+                        }
+                    }
+                    let (left, right) = self.bodies.split_at_mut(j);
+                    left[i].absorb(&right[0]);
+                    if self.selected_body == Some(j) {
+                        self.selected_body = Some(i)
+                    }
+                    let absorbed = self.take_body(j).unwrap();
+                    self.record(vec![Edit::Changed(i, survivor), Edit::Removed(j, absorbed)]);
+                }
+            }
+            CollisionMode::Delete => {
+                let pairs = self.colliding_pairs();
+                collisions.extend(pairs.iter().map(|(i, j)| self.collision_event(*i, *j)));
+                let mut to_del: Vec<usize> = pairs.into_iter().flat_map(|(i, j)| [i, j]).collect();
+                to_del.sort_unstable();
+                to_del.dedup();
+                // Bodies can collide with more than one other, so everything deleted in a tick is undone together
+                let edits: Vec<Edit> = to_del
+                    .into_iter()
+                    .rev()
+                    .filter_map(|x| self.take_body(x).map(|body| Edit::Removed(x, body)))
+                    .collect();
+                if !edits.is_empty() {
+                    self.record(edits);
+                }
+            }
+            CollisionMode::Shatter => {
+                // Shattering changes the indices, so the pairs are found again after every shattered pair
+                while let Some((i, j)) = self.colliding_pairs().into_iter().find(|(i, j)| self.shatters(*i, *j)) {
+                    collisions.push(self.collision_event(i, j));
+                    self.shatter(i, j);
+                }
+                self.bounce_colliding(&mut collisions);
+            }
+        }
+        collisions
     }
 
-    // Physics
+    fn bounce_colliding(&mut self, collisions: &mut Vec<CollisionEvent>) {
+        // Resolving a collision moves the bodies, so each pair is checked again just before resolving it
+        self.collision_candidates().into_iter().for_each(|(i, j)| {
+            if self.bodies[i].intersects(&self.bodies[j]) {
+                if self.approach_speed(i, j) > 0.0 {
+                    collisions.push(self.collision_event(i, j));
+                }
+                self.resolve_collision(i, j);
+            }
+        });
+    }
 
-    pub fn physics_tick(&mut self) {
-        for _ in 0..self.physics_speed {
-            self.gravity_tick();
-            self.movement_tick();
-            self.collision_tick();
+    fn collision_event(&self, i: usize, j: usize) -> CollisionEvent {
+        let (body1, body2) = (&self.bodies[i], &self.bodies[j]);
+        let mass = match (body1.fixed, body2.fixed) {
+            (true, true) => 0.0,
+            (true, false) => body2.mass,
+            (false, true) => body1.mass,
+            (false, false) if body1.mass + body2.mass > 0.0 => body1.mass * body2.mass / (body1.mass + body2.mass),
+            (false, false) => 0.0,
+        };
+        let contact = match body1.radius + body2.radius {
+            x if x > 0.0 => body1.radius / x,
+            _ => 0.5,
+        };
+        CollisionEvent {
+            pos: body1.pos + (body2.pos - body1.pos) * contact,
+            speed: self.approach_speed(i, j).max(0.0),
+            mass,
         }
     }
 
-    pub fn movement_tick(&mut self) {
-        self.bodies.iter_mut().for_each(|x| {
-            x.move_self();
-            x.add_trail();
-        })
+    // Speed at which the bodies move towards each other along the line between their centers, negative if they
+    // move apart
+    fn approach_speed(&self, i: usize, j: usize) -> Scalar {
+        let (body1, body2) = (&self.bodies[i], &self.bodies[j]);
+        let normal = (body2.pos - body1.pos).normalise();
+        Vector2D::dot(body1.velocity.0 - body2.velocity.0, normal)
     }
 
-    pub fn gravity_tick(&mut self) {
-        let bodies = self.bodies.to_vec();
+    // Fixed bodies never shatter, and neither do bodies whose fragments would be lighter than MIN_BODY_MASS
+    fn shatters(&self, i: usize, j: usize) -> bool {
+        let (body1, body2) = (&self.bodies[i], &self.bodies[j]);
+        !body1.fixed
+            && !body2.fixed
+            && (body1.mass + body2.mass) / SHATTER_FRAGMENTS as Scalar >= MIN_BODY_MASS
+            && self.bodies.len() - 2 + SHATTER_FRAGMENTS <= MAX_SHATTER_BODIES
+            && self.approach_speed(i, j) > SHATTER_SPEED
+    }
 
-        (0..self.bodies.len()).permutations(2).into_iter().for_each(|x| {
-            let grav_force_temp = -self.gravity_between(&bodies[x[0]], &bodies[x[1]]);
-            let self_momentum_temp = self.bodies[x[0]].momentum;
-            self.bodies[x[0]].set_momentum(self_momentum_temp + grav_force_temp);
-        })
+    // Replaces both bodies with SHATTER_FRAGMENTS equal fragments on a ring around their center of mass, flying
+    // outwards from it. The fragments alternate between the colors and groups of the two bodies, mass and momentum
+    // are conserved
+    fn shatter(&mut self, i: usize, j: usize) {
+        let spread = self.approach_speed(i, j) * SHATTER_SPREAD;
+        let body2 = self.take_body(j).unwrap();
+        let body1 = self.take_body(i).unwrap();
+        let total_mass = body1.mass + body2.mass;
+        let center = (body1.pos * body1.mass + body2.pos * body2.mass) / total_mass;
+        let velocity = (body1.velocity.0 * body1.mass + body2.velocity.0 * body2.mass) / total_mass;
+        let start_angle = (body2.pos.y - body1.pos.y).atan2(body2.pos.x - body1.pos.x);
+
+        let mass = total_mass / SHATTER_FRAGMENTS as Scalar;
+        // Just far enough out that neighbouring fragments don't overlap
+        let ring_radius = PhysicsBody::radius_from_mass(mass) / (TAU / (2 * SHATTER_FRAGMENTS) as Scalar).sin();
+        let debris_color = self.collision_visuals.debris_color;
+        let first = self.bodies.len();
+        self.bodies.extend((0..SHATTER_FRAGMENTS).map(|k| {
+            let angle = start_angle + TAU * k as Scalar / SHATTER_FRAGMENTS as Scalar;
+            let direction = Vector2D::new(angle.cos(), angle.sin());
+            let parent = if k % 2 == 0 { &body1 } else { &body2 };
+            PhysicsBody::builder()
+                .pos(center + direction * ring_radius)
+                .mass(mass)
+                .velocity(velocity + direction * spread)
+                .color(debris_color.unwrap_or(parent.color))
+                .group(parent.group)
+                .build()
+        }));
+
+        let mut edits = vec![Edit::Removed(j, body2), Edit::Removed(i, body1)];
+        edits.extend((first..self.bodies.len()).map(Edit::Added));
+        self.record(edits);
     }
 
-    pub fn collision_tick(&mut self) {
-        let bodies = self.bodies.to_vec();
-        match self.collision_mode {
-            CollisionMode::None => {}
-            CollisionMode::Collide => unimplemented!(),
-            CollisionMode::Absorb => unimplemented!(),
-            CollisionMode::Delete => {
-                let mut to_del: Vec<usize> = vec![];
-                (0..self.bodies.len()).combinations(2).into_iter().for_each(|x| {
-                    if bodies[x[0]].intersects(&bodies[x[1]]) {
-                        to_del.push(x[0]);
-                        to_del.push(x[1]);
-                    }
-                });
-                to_del.dedup();
-                to_del.into_iter().rev().for_each(|x| {
-                    self.bodies.remove(x);
-                });
-            }
+    // Impulse along the line between the centers, then separate the bodies by their overlap
+    fn resolve_collision(&mut self, i: usize, j: usize) {
+        let (body1, body2) = (&self.bodies[i], &self.bodies[j]);
+        let normal = (body2.pos - body1.pos).normalise();
+        let total_mass = body1.mass + body2.mass;
+        // Fixed bodies act as if their mass was infinite
+        let (share1, share2) = match (body1.fixed, body2.fixed) {
+            (true, true) => (0.0, 0.0),
+            (true, false) => (0.0, 1.0),
+            (false, true) => (1.0, 0.0),
+            (false, false) if total_mass > 0.0 => (body2.mass / total_mass, body1.mass / total_mass),
+            (false, false) => (0.5, 0.5),
+        };
+
+        let (velocity1, velocity2) = (body1.velocity.0, body2.velocity.0);
+        let approach_speed = Vector2D::dot(velocity1 - velocity2, normal);
+        if approach_speed > 0.0 {
+            // Velocity change of each body is the impulse divided by its mass
+            let delta = normal * ((1.0 + self.restitution) * approach_speed);
+            self.bodies[i].velocity = Velocity(velocity1 - delta * share1);
+            self.bodies[j].velocity = Velocity(velocity2 + delta * share2);
+        }
+
+        let overlap = self.bodies[i].radius + self.bodies[j].radius - self.bodies[i].distance_between(&self.bodies[j]);
+        if overlap > 0.0 {
+            self.bodies[i].pos -= normal * (overlap * share1);
+            self.bodies[j].pos += normal * (overlap * share2);
         }
     }
 
     pub fn handle_user_input(&mut self, input: SimulationInput) {
+        let mouse_world_pos = input.mouse_world_pos.map(from_f32_vec);
+        let min_pick_radius = Scalar::from(input.min_pick_radius);
+        if input.adjust_mass {
+            if let (Some(mouse_world_pos), Some(amount)) = (mouse_world_pos, input.mouse_scroll_wheel) {
+                if let Some(index) = self.get_body_on_point_index(mouse_world_pos, min_pick_radius) {
+                    let body = &mut self.bodies[index];
+                    body.set_mass((body.mass * MASS_SCROLL_FACTOR.powf(Scalar::from(amount))).max(MIN_BODY_MASS));
+                }
+            }
+        }
         if input.add_body {
-            if let Some(mouse_world_pos) = input.mouse_world_pos {
+            if let Some(mouse_world_pos) = mouse_world_pos {
                 let mut new_physics_body = PhysicsBody::new_rand();
                 new_physics_body.set_pos(mouse_world_pos);
                 self.add_body(new_physics_body);
             }
         }
+        let mouse_world_delta = input.mouse_world_delta.map(from_f32_vec);
+        match (self.dragged_body, input.mouse_left_down) {
+            (None, true) if self.pending_body.is_none() => {
+                if let Some(index) = mouse_world_pos.and_then(|x| self.get_body_on_point_index(x, min_pick_radius)) {
+                    let body = &mut self.bodies[index];
+                    self.dragged_body = Some((index, body.fixed));
+                    self.drag_velocity = Vector2D::new(0.0, 0.0);
+                    if self.freeze_dragged_body {
+                        body.set_fixed(true)
+                    }
+                }
+            }
+            (Some((index, _)), true) => {
+                if let Some(body) = self.bodies.get_mut(index) {
+                    if let Some(mouse_world_pos) = mouse_world_pos {
+                        body.set_pos(mouse_world_pos);
+                    }
+                    body.set_velocity(Velocity(Vector2D::new(0.0, 0.0)));
+                }
+                // Time covered by the physics ticks of one frame, none while physics_speed is 0
+                let frame_time = self.physics_speed as Scalar * self.dt * self.time_scale;
+                if let Some(mouse_world_delta) = mouse_world_delta.filter(|_| frame_time > 0.0) {
+                    self.drag_velocity +=
+                        (mouse_world_delta / frame_time - self.drag_velocity) * DRAG_VELOCITY_SMOOTHING;
+                }
+            }
+            (Some((index, was_fixed)), false) => {
+                self.dragged_body = None;
+                if let Some(body) = self.bodies.get_mut(index) {
+                    if self.freeze_dragged_body {
+                        body.set_fixed(was_fixed)
+                    }
+                    if !body.fixed {
+                        body.set_velocity(Velocity(self.drag_velocity))
+                    }
+                }
+            }
+            _ => {}
+        }
+        match (
+            &mut self.pending_body,
+            input.mouse_left_down && self.dragged_body.is_none(),
+        ) {
+            (None, true) => {
+                if let Some(mouse_world_pos) = mouse_world_pos {
+                    let mut new_physics_body = PhysicsBody::new_rand();
+                    new_physics_body.set_pos(mouse_world_pos);
+                    new_physics_body.set_velocity(Velocity(Vector2D::new(0.0, 0.0)));
+                    self.pending_body = Some(new_physics_body);
+                }
+            }
+            (Some(pending_body), true) => {
+                if let Some(mouse_world_pos) = mouse_world_pos {
+                    pending_body.set_velocity(Velocity((mouse_world_pos - pending_body.pos) / VELOCITY_ARROW_SCALE));
+                }
+            }
+            (Some(_), false) => {
+                let new_physics_body = self.pending_body.take().unwrap();
+                self.add_body(new_physics_body);
+            }
+            (None, false) => {}
+        }
         if input.remove_body {
-            if let Some(mouse_world_pos) = input.mouse_world_pos {
-                if let Some(index) = self.get_body_on_point_index(mouse_world_pos) {
+            if let Some(mouse_world_pos) = mouse_world_pos {
+                if let Some(index) = self.get_body_on_point_index(mouse_world_pos, min_pick_radius) {
                     self.remove_body(index);
                 }
             }
         }
         if input.print_body {
-            if let Some(mouse_world_pos) = input.mouse_world_pos {
-                let found = self.get_bodies_on_point(mouse_world_pos);
+            if let Some(mouse_world_pos) = mouse_world_pos {
+                let found = self.get_bodies_on_point(mouse_world_pos, min_pick_radius);
                 if !found.is_empty() {
                     println!("{:} bodies under cursor: ", found.len());
                     found.into_iter().for_each(|x| println!("{x}"));
@@ -223,8 +2021,38 @@ impl Simulation {
             }
         }
         if input.selected_body {
-            if let Some(mouse_world_pos) = input.mouse_world_pos {
-                self.selected_body = self.get_body_on_point_index(mouse_world_pos);
+            if let Some(mouse_world_pos) = mouse_world_pos {
+                self.selected_body = self.get_body_on_point_index(mouse_world_pos, min_pick_radius);
+            }
+        }
+
+        if input.delete_selected {
+            if let Some(selected_body) = self.selected_body {
+                self.remove_body(selected_body)
+            }
+        }
+        if input.duplicate_selected {
+            if let Some(body) = self.selected_body.and_then(|x| self.get_body(x)) {
+                let mut duplicate = body.clone();
+                duplicate.pos.x += duplicate.radius * DUPLICATE_OFFSET;
+                duplicate.trail.clear();
+                self.add_body(duplicate);
+                self.selected_body = Some(self.bodies.len() - 1);
+            }
+        }
+
+        if input.undo {
+            self.undo()
+        }
+        if input.redo {
+            self.redo()
+        }
+
+        if input.toggle_fixed {
+            if let Some(selected_body) = self.selected_body {
+                if let Some(body) = self.bodies.get_mut(selected_body) {
+                    body.set_fixed(!body.fixed)
+                }
             }
         }
 
@@ -232,74 +2060,185 @@ impl Simulation {
             self.set_physics_speed(self.physics_speed + 1)
         }
         if input.down_speed {
-            self.set_physics_speed(if self.physics_speed > 0 {
-                self.physics_speed - 1
-            } else {
-                1
-            })
+            self.set_physics_speed(self.physics_speed.saturating_sub(1))
+        }
+        if input.halve_time_scale {
+            self.set_time_scale(self.time_scale / 2.0)
+        }
+        if input.double_time_scale {
+            self.set_time_scale(self.time_scale * 2.0)
+        }
+        if input.lower_restitution {
+            self.set_restitution(self.restitution - RESTITUTION_STEP)
+        }
+        if input.raise_restitution {
+            self.set_restitution(self.restitution + RESTITUTION_STEP)
+        }
+        if input.increase_grav_const {
+            self.set_grav_const(self.grav_const * GRAV_CONST_STEP)
+        }
+        if input.decrease_grav_const {
+            self.set_grav_const(self.grav_const / GRAV_CONST_STEP)
+        }
+        if input.flip_gravity {
+            self.grav_const = -self.grav_const
+        }
+
+        if input.toggle_prediction_cone {
+            self.show_prediction_cone = !self.show_prediction_cone
+        }
+        if input.toggle_center_of_mass {
+            self.show_center_of_mass = !self.show_center_of_mass
+        }
+        if input.toggle_force_arrows {
+            self.show_force_arrows = !self.show_force_arrows
+        }
+        if input.cycle_color_mode {
+            self.color_mode = self.color_mode.next()
+        }
+
+        if input.clear_trails {
+            self.clear_trails()
+        }
+
+        if input.cycle_trail_length {
+            let next = TRAIL_LENGTH_PRESETS
+                .iter()
+                .position(|x| *x == self.max_trail_length)
+                .map_or(0, |i| (i + 1) % TRAIL_LENGTH_PRESETS.len());
+            self.set_max_trail_length(TRAIL_LENGTH_PRESETS[next]);
+        }
+
+        if input.cycle_trail_style {
+            self.trail_style = self.trail_style.next()
+        }
+
+        if input.toggle_trail_fade {
+            self.trail_fade = !self.trail_fade
+        }
+
+        if input.toggle_trail_blend_mode {
+            self.trail_blend_mode = match self.trail_blend_mode {
+                graphics::BlendMode::Replace => graphics::BlendMode::Additive,
+                graphics::BlendMode::Additive | graphics::BlendMode::Alpha(_) => graphics::BlendMode::Replace,
+            }
+        }
+
+        if input.load_figure_eight {
+            self.load_preset(Simulation::preset_figure_eight())
+        }
+
+        if input.load_orbits {
+            self.load_preset(Simulation::preset_orbits(self.num_of_bodies))
         }
 
         if input.reset_contents {
             self.selected_body = None;
-            self.bodies = (0..NUM_OF_BODIES)
-                .into_iter()
-                .map(|_| PhysicsBody::new_rand())
-                .collect();
+            self.dragged_body = None;
+            self.clear_history();
+            self.bodies = Simulation::random_bodies(self.num_of_bodies, self.seed);
         }
     }
 }
 
 // ----------------------------------------------------------------
 
-#[derive(Debug, Copy, Clone, PartialEq)]
+// A change to which bodies exist, as kept for undo. Indices are into the bodies at the time of the change
+#[derive(Debug, Clone, PartialEq)]
+enum Edit {
+    Added(usize),
+    Removed(usize, PhysicsBody),
+    // The body at the index was replaced, holds the one it replaced
+    Changed(usize, PhysicsBody),
+}
+
+// ----------------------------------------------------------------
+
+// Everything needed to recreate a simulation, saved to and loaded from JSON
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SimulationState {
+    pub bodies: Vec<PhysicsBody>,
+    pub grav_const: Scalar,
+    pub physics_speed: u32,
+    pub collision_mode: CollisionMode,
+    // Missing from scenes saved before groups existed
+    #[serde(default)]
+    pub gravity_matrix: GravityMatrix,
+    #[serde(default)]
+    pub collision_visuals: CollisionVisuals,
+}
+
+#[allow(dead_code)]
+impl SimulationState {
+    // Constructor
+    pub fn load(path: impl AsRef<Path>) -> io::Result<SimulationState> {
+        Ok(serde_json::from_str(&fs::read_to_string(path)?)?)
+    }
+
+    // Methods
+    pub fn save(&self, path: impl AsRef<Path>) -> io::Result<()> {
+        fs::write(path, serde_json::to_string_pretty(self)?)
+    }
+}
+
+// ----------------------------------------------------------------
+
+#[derive(Debug, Copy, Clone, PartialEq, Serialize, Deserialize)]
 pub struct Force {
-    direction: Vector2D<f32>,
-    amplitude: f32,
+    #[serde(with = "serde_vector2d")]
+    direction: Vector2D<Scalar>,
+    amplitude: Scalar,
 }
 
 #[allow(dead_code)]
 impl Force {
     // Constructor
-    pub fn new(direction: Vector2D<f32>, amplitude: f32) -> Force {
+    pub fn new(direction: Vector2D<Scalar>, amplitude: Scalar) -> Force {
         Force {
             direction: direction.normalise(),
-            amplitude: amplitude.abs().clamp(0.0, MAX_FORCE_AMPLITUDE.unwrap_or(f32::MAX)),
+            amplitude: amplitude.abs(),
         }
     }
 
+    pub fn from_vector2d(val: Vector2D<Scalar>) -> Force {
+        Force::new(val, val.length())
+    }
+
     pub fn new_rand() -> Force {
-        let mut rng = rand::thread_rng();
+        Force::new_rand_with(&mut rand::thread_rng())
+    }
 
+    pub fn new_rand_with(rng: &mut impl Rng) -> Force {
         Force {
             direction: Vector2D::new(
-                rng.gen::<f32>() * if rng.gen() { -1.0 } else { 1.0 },
-                rng.gen::<f32>() * if rng.gen() { -1.0 } else { 1.0 },
+                rng.gen::<Scalar>() * if rng.gen() { -1.0 } else { 1.0 },
+                rng.gen::<Scalar>() * if rng.gen() { -1.0 } else { 1.0 },
             )
             .normalise(),
-            amplitude: rng.gen::<f32>().abs(),
+            amplitude: rng.gen::<Scalar>().abs(),
         }
     }
 
     // Immutable access
-    pub fn direction(&self) -> &Vector2D<f32> {
+    pub fn direction(&self) -> &Vector2D<Scalar> {
         &self.direction
     }
 
-    pub fn amplitude(&self) -> &f32 {
+    pub fn amplitude(&self) -> &Scalar {
         &self.amplitude
     }
 
     // Setters
-    pub fn set_direction(&mut self, val: Vector2D<f32>) {
+    pub fn set_direction(&mut self, val: Vector2D<Scalar>) {
         self.direction = val.normalise()
     }
 
-    pub fn set_amplitude(&mut self, val: f32) {
-        self.amplitude = val.abs().clamp(0.0, MAX_FORCE_AMPLITUDE.unwrap_or(f32::MAX))
+    pub fn set_amplitude(&mut self, val: Scalar) {
+        self.amplitude = val.abs()
     }
 
     // Methods
-    pub fn as_vector2d(&self) -> Vector2D<f32> {
+    pub fn as_vector2d(&self) -> Vector2D<Scalar> {
         Vector2D::new(self.direction.x * self.amplitude, self.direction.y * self.amplitude)
     }
 }
@@ -308,14 +2247,7 @@ impl std::ops::Add for Force {
     type Output = Self;
 
     fn add(self, other: Self) -> Self {
-        let out_vector = Vector2D::new(self.direction.x * self.amplitude, self.direction.y * self.amplitude)
-            + Vector2D::new(other.direction.x * other.amplitude, other.direction.y * other.amplitude);
-        let normal = out_vector.normalise();
-
-        Self::new(
-            normal,
-            out_vector.length() / if normal.length() != 0.0 { normal.length() } else { 1.0 },
-        )
+        Self::from_vector2d(self.as_vector2d() + other.as_vector2d())
     }
 }
 
@@ -325,6 +2257,14 @@ impl std::ops::AddAssign for Force {
     }
 }
 
+impl std::ops::Mul<Scalar> for Force {
+    type Output = Self;
+
+    fn mul(self, other: Scalar) -> Self {
+        Self::new(self.direction, self.amplitude * other)
+    }
+}
+
 impl std::ops::Neg for Force {
     type Output = Self;
 
@@ -338,153 +2278,506 @@ impl std::ops::Neg for Force {
 
 // ----------------------------------------------------------------
 
-#[derive(Debug, Clone, PartialEq)]
+// How far a body moves per tick, in world units. This is the displacement itself and not momentum, mass doesn't enter
+// into it: movement_tick moves a body by velocity * dt and gravity changes the velocity by force / mass * dt
+#[derive(Debug, Copy, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Velocity(#[serde(with = "serde_vector2d")] pub Vector2D<Scalar>);
+
+#[allow(dead_code)]
+impl Velocity {
+    // Constructor
+    pub fn new_rand() -> Velocity {
+        Velocity::new_rand_with(&mut rand::thread_rng())
+    }
+
+    pub fn new_rand_with(rng: &mut impl Rng) -> Velocity {
+        let direction = Vector2D::new(
+            rng.gen::<Scalar>() * if rng.gen() { -1.0 } else { 1.0 },
+            rng.gen::<Scalar>() * if rng.gen() { -1.0 } else { 1.0 },
+        )
+        .normalise();
+
+        Velocity(direction * rng.gen::<Scalar>())
+    }
+
+    // Methods
+    pub fn speed(&self) -> Scalar {
+        self.0.length()
+    }
+}
+
+impl std::ops::Add for Velocity {
+    type Output = Self;
+
+    fn add(self, other: Self) -> Self {
+        Velocity(self.0 + other.0)
+    }
+}
+
+impl std::ops::AddAssign for Velocity {
+    fn add_assign(&mut self, other: Self) {
+        *self = *self + other;
+    }
+}
+
+// ----------------------------------------------------------------
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct PhysicsBody {
-    pos: Vector2D<f32>,
-    mass: f32,
-    radius: f32,
-    momentum: Force,
+    #[serde(with = "serde_vector2d")]
+    pos: Vector2D<Scalar>,
+    mass: Scalar,
+    radius: Scalar,
+    velocity: Velocity,
+    // Last applied acceleration, used by the velocity Verlet integrator. None until gravity has been applied once
+    #[serde(skip)]
+    acceleration: Option<Vector2D<Scalar>>,
     color: graphics::Color,
-    trail: Vec<Vector2D<f32>>,
+    #[serde(default, with = "serde_vector2d::vec")]
+    trail: Vec<Vector2D<Scalar>>,
+    // Fixed bodies attract others but never move
+    #[serde(default)]
+    fixed: bool,
+    // Whether bodies of two groups attract each other is set by the simulation's gravity matrix
+    #[serde(default)]
+    group: u8,
 }
 
 #[allow(dead_code)]
 impl PhysicsBody {
     // Constructor
-    pub fn new(pos: Vector2D<f32>, mass: f32, momentum: Force, color: graphics::Color) -> PhysicsBody {
+    pub fn new(pos: Vector2D<Scalar>, mass: Scalar, velocity: Velocity, color: graphics::Color) -> PhysicsBody {
         PhysicsBody {
             pos,
             mass,
-            radius: mass / 5.0,
-            momentum,
+            radius: PhysicsBody::radius_from_mass(mass),
+            velocity,
+            acceleration: None,
             color,
-            trail: Vec::with_capacity(MAX_TRAIL_LENGTH.unwrap_or(255)),
+            trail: Vec::with_capacity(DEFAULT_MAX_TRAIL_LENGTH.unwrap_or(255)),
+            fixed: false,
+            group: 0,
         }
     }
 
+    pub fn builder() -> PhysicsBodyBuilder {
+        PhysicsBodyBuilder::new()
+    }
+
     pub fn new_rand() -> PhysicsBody {
-        let mut rng = rand::thread_rng();
-        let mass = rng.gen::<f32>() * 50.0;
+        PhysicsBody::new_rand_with(&mut rand::thread_rng())
+    }
+
+    pub fn new_rand_with(rng: &mut impl Rng) -> PhysicsBody {
+        let mass = rng.gen::<Scalar>() * 50.0;
 
         PhysicsBody {
-            pos: Vector2D::new(rng.gen::<f32>() * 500.0, rng.gen::<f32>() * 500.0),
+            pos: Vector2D::new(rng.gen::<Scalar>() * 500.0, rng.gen::<Scalar>() * 500.0),
             mass,
-            radius: mass / 5.0,
-            momentum: Force::new_rand(),
+            radius: PhysicsBody::radius_from_mass(mass),
+            velocity: Velocity::new_rand_with(rng),
+            acceleration: None,
             color: graphics::Color::new(
-                (10.0 + rng.gen::<f32>() * 245.0) as u8,
-                (10.0 + rng.gen::<f32>() * 245.0) as u8,
-                (10.0 + rng.gen::<f32>() * 245.0) as u8,
+                (10.0 + rng.gen::<Scalar>() * 245.0) as u8,
+                (10.0 + rng.gen::<Scalar>() * 245.0) as u8,
+                (10.0 + rng.gen::<Scalar>() * 245.0) as u8,
             ),
-            trail: Vec::with_capacity(MAX_TRAIL_LENGTH.unwrap_or(255)),
+            trail: Vec::with_capacity(DEFAULT_MAX_TRAIL_LENGTH.unwrap_or(255)),
+            fixed: false,
+            group: 0,
         }
     }
 
     // Immutable access
-    pub fn pos(&self) -> &Vector2D<f32> {
+    pub fn pos(&self) -> &Vector2D<Scalar> {
         &self.pos
     }
 
-    pub fn mass(&self) -> &f32 {
+    pub fn mass(&self) -> &Scalar {
         &self.mass
     }
 
-    pub fn momentum(&self) -> &Force {
-        &self.momentum
+    pub fn radius(&self) -> &Scalar {
+        &self.radius
+    }
+
+    // Displacement per tick, times mass for the momentum
+    pub fn velocity(&self) -> &Velocity {
+        &self.velocity
+    }
+
+    pub fn acceleration(&self) -> &Option<Vector2D<Scalar>> {
+        &self.acceleration
     }
 
     pub fn color(&self) -> &graphics::Color {
         &self.color
     }
 
-    pub fn trail(&self) -> &Vec<Vector2D<f32>> {
+    pub fn fixed(&self) -> &bool {
+        &self.fixed
+    }
+
+    pub fn group(&self) -> &u8 {
+        &self.group
+    }
+
+    pub fn trail(&self) -> &Vec<Vector2D<Scalar>> {
         &self.trail
     }
 
     // Setters
-    pub fn set_pos(&mut self, val: Vector2D<f32>) {
+    pub fn set_pos(&mut self, val: Vector2D<Scalar>) {
         self.pos = val
     }
-    pub fn set_mass(&mut self, val: f32) {
-        self.mass = val.abs()
+    // Also resizes the body to match
+    pub fn set_mass(&mut self, val: Scalar) {
+        self.mass = val.abs();
+        self.radius = PhysicsBody::radius_from_mass(self.mass)
     }
-    pub fn set_momentum(&mut self, val: Force) {
-        self.momentum = val
+    pub fn set_velocity(&mut self, val: Velocity) {
+        self.velocity = val
     }
     pub fn set_color(&mut self, val: graphics::Color) {
         self.color = val
     }
+    // Fixing a body also stops it
+    pub fn set_fixed(&mut self, val: bool) {
+        self.fixed = val;
+        if val {
+            self.velocity = Velocity(Vector2D::new(0.0, 0.0));
+            self.acceleration = None;
+        }
+    }
+    pub fn set_group(&mut self, val: u8) {
+        self.group = val
+    }
 
     // Methods
-    pub fn move_self(&mut self) {
-        self.pos += self.momentum.as_vector2d();
+    pub fn radius_from_mass(mass: Scalar) -> Scalar {
+        mass / MASS_PER_RADIUS
+    }
+
+    // Whether gravity_tick accelerates the body. Fixed bodies stay where they are, and a massless one feels no force
+    // so it keeps moving as it is
+    fn accelerated(&self) -> bool {
+        self.mass > 0.0 && !self.fixed
+    }
+
+    fn clamp_acceleration(acceleration: Vector2D<Scalar>) -> Vector2D<Scalar> {
+        match MAX_ACCELERATION {
+            Some(max) if acceleration.length() > max => acceleration.normalise() * max,
+            _ => acceleration,
+        }
+    }
+
+    pub fn move_self(&mut self, dt: Scalar) {
+        self.pos += self.velocity.0 * dt;
+    }
+
+    pub fn move_self_verlet(&mut self, dt: Scalar) {
+        self.pos += self.velocity.0 * dt + self.acceleration.unwrap_or(Vector2D::new(0.0, 0.0)) * (0.5 * dt * dt);
+    }
+
+    pub fn accelerate(&mut self, acceleration: Vector2D<Scalar>, dt: Scalar) {
+        let acceleration = PhysicsBody::clamp_acceleration(acceleration);
+        self.velocity += Velocity(acceleration * dt);
+        self.acceleration = Some(acceleration);
+    }
+
+    pub fn accelerate_verlet(&mut self, acceleration: Vector2D<Scalar>, dt: Scalar) {
+        let acceleration = PhysicsBody::clamp_acceleration(acceleration);
+        self.velocity += Velocity((self.acceleration.unwrap_or(acceleration) + acceleration) * (0.5 * dt));
+        self.acceleration = Some(acceleration);
+    }
+
+    pub fn confine(&mut self, bounds: &Bounds, mode: BoundaryMode, restitution: Scalar) {
+        match mode {
+            BoundaryMode::None => {}
+            BoundaryMode::Bounce => self.bounce_off(bounds, restitution),
+            BoundaryMode::Wrap => self.wrap_around(bounds),
+        }
+    }
+
+    // The body bounces when its edge touches a wall, it is moved back inside and its velocity towards the wall is
+    // reversed and scaled by restitution
+    pub fn bounce_off(&mut self, bounds: &Bounds, restitution: Scalar) {
+        let (mut pos, mut velocity) = ([self.pos.x, self.pos.y], [self.velocity.0.x, self.velocity.0.y]);
+        let (min, max) = ([bounds.min.x, bounds.min.y], [bounds.max.x, bounds.max.y]);
+        for axis in 0..2 {
+            if pos[axis] - self.radius < min[axis] {
+                pos[axis] = min[axis] + self.radius;
+                if velocity[axis] < 0.0 {
+                    velocity[axis] = -velocity[axis] * restitution;
+                }
+            } else if pos[axis] + self.radius > max[axis] {
+                pos[axis] = max[axis] - self.radius;
+                if velocity[axis] > 0.0 {
+                    velocity[axis] = -velocity[axis] * restitution;
+                }
+            }
+        }
+        self.pos = Vector2D::new(pos[0], pos[1]);
+        self.velocity = Velocity(Vector2D::new(velocity[0], velocity[1]));
+    }
+
+    // The body's center reappears on the opposite edge, its trail is cleared so it isn't drawn across the bounds
+    pub fn wrap_around(&mut self, bounds: &Bounds) {
+        let size = bounds.size();
+        let wrap = |p: Scalar, min: Scalar, size: Scalar| {
+            if size > 0.0 {
+                min + (p - min).rem_euclid(size)
+            } else {
+                p
+            }
+        };
+        let wrapped = Vector2D::new(
+            wrap(self.pos.x, bounds.min.x, size.x),
+            wrap(self.pos.y, bounds.min.y, size.y),
+        );
+        if wrapped != self.pos {
+            self.pos = wrapped;
+            self.trail.clear();
+        }
     }
 
-    pub fn add_trail(&mut self) {
+    pub fn add_trail(&mut self, max_length: Option<usize>) {
         self.trail.push(self.pos);
+        self.truncate_trail(max_length);
+    }
 
-        if let Some(v) = MAX_TRAIL_LENGTH {
+    // Removes the oldest points so at most max_length are left
+    pub fn truncate_trail(&mut self, max_length: Option<usize>) {
+        if let Some(v) = max_length {
             if self.trail.len() > v {
-                self.trail.remove(0);
+                self.trail.drain(..self.trail.len() - v);
             }
-        } else {
         }
     }
 
-    pub fn shape(&self) -> Vec<Box<dyn graphics::Draw>> {
+    pub fn shape(
+        &self,
+        theme: &graphics::Theme,
+        trail_blend_mode: graphics::BlendMode,
+    ) -> Vec<Box<dyn graphics::Draw>> {
+        self.shape_with_color(theme, trail_blend_mode, false, TrailStyle::Lines, self.color)
+    }
+
+    // Draws the body and its trail in color instead of its own color
+    pub fn shape_with_color(
+        &self,
+        theme: &graphics::Theme,
+        trail_blend_mode: graphics::BlendMode,
+        trail_fade: bool,
+        trail_style: TrailStyle,
+        color: graphics::Color,
+    ) -> Vec<Box<dyn graphics::Draw>> {
+        let arrow_end = Vector2D::new(
+            self.pos.x + (self.velocity.0.x * VELOCITY_ARROW_SCALE),
+            self.pos.y + (self.velocity.0.y * VELOCITY_ARROW_SCALE),
+        );
         let mut out: Vec<Box<dyn graphics::Draw>> = vec![
-            Box::new(graphics::Circle::new(self.pos, self.radius, 1, self.color)),
+            Box::new(graphics::Circle::new(
+                to_f32_vec(self.pos),
+                to_f32(self.radius),
+                1,
+                color,
+            )),
             Box::new(graphics::Line::new(
-                self.pos,
-                Vector2D::new(
-                    self.pos.x + (self.momentum.direction().x * self.momentum.amplitude() * 20.0),
-                    self.pos.y + (self.momentum.direction().y * self.momentum.amplitude() * 20.0),
-                ),
+                to_f32_vec(self.pos),
+                to_f32_vec(arrow_end),
                 2,
-                graphics::Color::new(255, 255, 255),
+                theme.arrow,
             )),
         ];
-        for i in 1..self.trail.len() {
-            out.push(Box::new(graphics::Line::new(
-                self.trail[i - 1],
-                self.trail[i],
-                0,
-                self.color,
+        let arrow_length = (arrow_end - self.pos).length();
+        if arrow_length > 0.0 {
+            let head_scale = (arrow_length / (2.0 * ARROWHEAD_LENGTH)).min(1.0);
+            out.push(Box::new(graphics::Triangle::arrowhead(
+                to_f32_vec(arrow_end),
+                to_f32_vec(self.velocity.0),
+                to_f32(ARROWHEAD_LENGTH * head_scale),
+                to_f32(ARROWHEAD_WIDTH * head_scale),
+                2,
+                theme.arrow,
             )))
         }
-        // for i in 2..self.trail.len() {
-        //     if i % 2 == 0 {
-        //         out.push(Box::new(graphics::Line::new(
-        //             self.trail[i - 1],
-        //             self.trail[i],
-        //             0,
-        //             self.color,
-        //         )))
-        //     }
-        // }
+        let trail_color = color.scaled(theme.trail_brightness);
+        match trail_style {
+            TrailStyle::Lines => {
+                if self.trail.len() > 1 {
+                    let mut trail =
+                        graphics::Polyline::new(self.trail.iter().map(|x| to_f32_vec(*x)).collect(), 0, trail_color);
+                    // The newest point is at the end of the trail
+                    if trail_fade {
+                        trail.set_fade_from(Some(theme.background));
+                    }
+                    trail.set_blend_mode(trail_blend_mode);
+                    out.push(Box::new(trail))
+                }
+            }
+            // Dots always fade, the oldest is the dimmest without disappearing. With a radius of 0 they are drawn at
+            // the scene's smallest body size whatever the zoom
+            TrailStyle::Dots => {
+                for (i, point) in self.trail.iter().enumerate() {
+                    let dot_color = theme
+                        .background
+                        .lerp(trail_color, (i + 1) as f32 / self.trail.len() as f32);
+                    out.push(Box::new(graphics::Circle::new(to_f32_vec(*point), 0.0, 0, dot_color)))
+                }
+            }
+            TrailStyle::None => {}
+        }
         out
     }
 
+    // Conserves mass and linear momentum, position and color are mass-weighted. Absorbing into or by a fixed body
+    // leaves a fixed body where the fixed one was
+    pub fn absorb(&mut self, other: &PhysicsBody) {
+        let total_mass = self.mass + other.mass;
+        let (share_self, share_other) = if total_mass > 0.0 {
+            (self.mass / total_mass, other.mass / total_mass)
+        } else {
+            (0.5, 0.5)
+        };
+        let blend = |a: u8, b: u8| (a as Scalar * share_self + b as Scalar * share_other).round() as u8;
+
+        self.pos = match (self.fixed, other.fixed) {
+            (true, _) => self.pos,
+            (false, true) => other.pos,
+            (false, false) => self.pos * share_self + other.pos * share_other,
+        };
+        self.velocity = Velocity(self.velocity.0 * share_self + other.velocity.0 * share_other);
+        self.acceleration = self
+            .acceleration
+            .zip(other.acceleration)
+            .map(|(a, b)| a * share_self + b * share_other);
+        self.color = graphics::Color::new(
+            blend(self.color.r, other.color.r),
+            blend(self.color.g, other.color.g),
+            blend(self.color.b, other.color.b),
+        );
+        self.mass = total_mass;
+        self.radius = PhysicsBody::radius_from_mass(total_mass);
+        if self.fixed || other.fixed {
+            self.set_fixed(true);
+        }
+    }
+
     pub fn intersects(&self, other: &Self) -> bool {
         self.distance_between(other) < (self.radius + other.radius)
     }
 
-    pub fn distance_between(&self, other: &Self) -> f32 {
+    pub fn distance_between(&self, other: &Self) -> Scalar {
         ((other.pos.x - self.pos.x).powf(2.0) + (other.pos.y - self.pos.y).powf(2.0)).sqrt()
     }
+
+    // Corners of a rectangle around the body, its velocity arrow and its trail, the smallest coordinates first
+    pub fn bounding_box(&self) -> (Vector2D<Scalar>, Vector2D<Scalar>) {
+        // Each point with how far its shape reaches past it
+        let arrow_end = self.pos + self.velocity.0 * VELOCITY_ARROW_SCALE;
+        [(self.pos, self.radius), (arrow_end, ARROWHEAD_WIDTH)]
+            .into_iter()
+            .chain(self.trail.iter().map(|x| (*x, 0.0)))
+            .fold(
+                (
+                    Vector2D::new(Scalar::MAX, Scalar::MAX),
+                    Vector2D::new(Scalar::MIN, Scalar::MIN),
+                ),
+                |(min, max), (p, reach)| {
+                    (
+                        Vector2D::new(min.x.min(p.x - reach), min.y.min(p.y - reach)),
+                        Vector2D::new(max.x.max(p.x + reach), max.y.max(p.y + reach)),
+                    )
+                },
+            )
+    }
 }
 
 impl fmt::Display for PhysicsBody {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         write!(
             f,
-            "{}Pos: {:?}, Mass: {:?}, Radius: {:?}, Momentum: {:?}{}",
+            "{}Pos: {:?}, Mass: {:?}, Radius: {:?}, Velocity: {:?}{}",
             self.color.bg_string(),
             self.pos,
             self.mass,
             self.radius,
-            self.momentum,
+            self.velocity.0,
             graphics::Color::default_color(),
         )
     }
 }
+
+// ----------------------------------------------------------------
+
+// Unset fields default to a resting white body of DEFAULT_BODY_MASS at the origin, the radius follows from the mass
+#[derive(Debug, Clone, PartialEq)]
+pub struct PhysicsBodyBuilder {
+    pos: Vector2D<Scalar>,
+    mass: Scalar,
+    velocity: Velocity,
+    color: graphics::Color,
+    fixed: bool,
+    group: u8,
+}
+
+impl Default for PhysicsBodyBuilder {
+    fn default() -> Self {
+        PhysicsBodyBuilder {
+            pos: Vector2D::new(0.0, 0.0),
+            mass: DEFAULT_BODY_MASS,
+            velocity: Velocity(Vector2D::new(0.0, 0.0)),
+            color: graphics::Color::new(255, 255, 255),
+            fixed: false,
+            group: 0,
+        }
+    }
+}
+
+#[allow(dead_code)]
+impl PhysicsBodyBuilder {
+    // Constructor
+    pub fn new() -> PhysicsBodyBuilder {
+        PhysicsBodyBuilder::default()
+    }
+
+    // Setters
+    pub fn pos(mut self, val: Vector2D<Scalar>) -> PhysicsBodyBuilder {
+        self.pos = val;
+        self
+    }
+
+    pub fn mass(mut self, val: Scalar) -> PhysicsBodyBuilder {
+        self.mass = val.abs();
+        self
+    }
+
+    pub fn velocity(mut self, val: Vector2D<Scalar>) -> PhysicsBodyBuilder {
+        self.velocity = Velocity(val);
+        self
+    }
+
+    pub fn color(mut self, val: graphics::Color) -> PhysicsBodyBuilder {
+        self.color = val;
+        self
+    }
+
+    pub fn fixed(mut self, val: bool) -> PhysicsBodyBuilder {
+        self.fixed = val;
+        self
+    }
+
+    pub fn group(mut self, val: u8) -> PhysicsBodyBuilder {
+        self.group = val;
+        self
+    }
+
+    // Methods
+    pub fn build(self) -> PhysicsBody {
+        let mut body = PhysicsBody::new(self.pos, self.mass, self.velocity, self.color);
+        body.set_fixed(self.fixed);
+        body.set_group(self.group);
+        body
+    }
+}
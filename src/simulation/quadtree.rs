@@ -0,0 +1,167 @@
+use super::Scalar;
+use vector2d::Vector2D;
+
+const MAX_DEPTH: u32 = 32;
+
+// ----------------------------------------------------------------
+
+// Leaves hold their points directly, points that can't be separated by MAX_DEPTH share a leaf
+struct Node {
+    center: Vector2D<Scalar>,
+    half_size: Scalar,
+    mass: Scalar,
+    weighted_pos: Vector2D<Scalar>,
+    children: Option<[usize; 4]>,
+    points: Vec<(usize, Vector2D<Scalar>, Scalar)>,
+}
+
+impl Node {
+    // Constructor
+    fn new(center: Vector2D<Scalar>, half_size: Scalar) -> Node {
+        Node {
+            center,
+            half_size,
+            mass: 0.0,
+            weighted_pos: Vector2D::new(0.0, 0.0),
+            children: None,
+            points: vec![],
+        }
+    }
+
+    // Methods
+    fn center_of_mass(&self) -> Vector2D<Scalar> {
+        self.weighted_pos / self.mass
+    }
+
+    fn contains(&self, p: Vector2D<Scalar>) -> bool {
+        (p.x - self.center.x).abs() <= self.half_size && (p.y - self.center.y).abs() <= self.half_size
+    }
+
+    fn quadrant(&self, p: Vector2D<Scalar>) -> usize {
+        (if p.x >= self.center.x { 1 } else { 0 }) + (if p.y >= self.center.y { 2 } else { 0 })
+    }
+}
+
+// ----------------------------------------------------------------
+
+pub struct QuadTree {
+    nodes: Vec<Node>,
+}
+
+impl QuadTree {
+    // Constructor
+    pub fn new(points: &[(Vector2D<Scalar>, Scalar)]) -> QuadTree {
+        let (min, max) = points.iter().fold(
+            (
+                Vector2D::new(Scalar::MAX, Scalar::MAX),
+                Vector2D::new(Scalar::MIN, Scalar::MIN),
+            ),
+            |(min, max), (p, _)| {
+                (
+                    Vector2D::new(min.x.min(p.x), min.y.min(p.y)),
+                    Vector2D::new(max.x.max(p.x), max.y.max(p.y)),
+                )
+            },
+        );
+        let (center, half_size) = if points.is_empty() {
+            (Vector2D::new(0.0, 0.0), 1.0)
+        } else {
+            ((min + max) / 2.0, ((max.x - min.x).max(max.y - min.y) / 2.0).max(1.0))
+        };
+
+        let mut out = QuadTree {
+            nodes: vec![Node::new(center, half_size)],
+        };
+        points
+            .iter()
+            .enumerate()
+            .for_each(|(i, (pos, mass))| out.insert(0, (i, *pos, *mass), 0));
+        out
+    }
+
+    // Methods
+    fn insert(&mut self, node: usize, point: (usize, Vector2D<Scalar>, Scalar), depth: u32) {
+        self.nodes[node].mass += point.2;
+        self.nodes[node].weighted_pos += point.1 * point.2;
+
+        match self.nodes[node].children {
+            Some(children) => {
+                let quadrant = self.nodes[node].quadrant(point.1);
+                self.insert(children[quadrant], point, depth + 1)
+            }
+            None => {
+                if self.nodes[node].points.is_empty() || depth >= MAX_DEPTH {
+                    self.nodes[node].points.push(point)
+                } else {
+                    self.subdivide(node);
+                    let children = self.nodes[node].children.unwrap();
+                    let points = std::mem::take(&mut self.nodes[node].points);
+                    points.into_iter().chain(std::iter::once(point)).for_each(|x| {
+                        let quadrant = self.nodes[node].quadrant(x.1);
+                        self.insert(children[quadrant], x, depth + 1)
+                    });
+                }
+            }
+        }
+    }
+
+    fn subdivide(&mut self, node: usize) {
+        let (center, half_size) = (self.nodes[node].center, self.nodes[node].half_size / 2.0);
+        let first = self.nodes.len();
+        for quadrant in 0..4 {
+            let offset = Vector2D::new(
+                if quadrant % 2 == 1 { half_size } else { -half_size },
+                if quadrant >= 2 { half_size } else { -half_size },
+            );
+            self.nodes.push(Node::new(center + offset, half_size));
+        }
+        self.nodes[node].children = Some([first, first + 1, first + 2, first + 3]);
+    }
+
+    // Sums `force(pos, mass, other_pos, other_mass)` over the tree, treating a cell as a single point mass when
+    // its size divided by its distance is below theta. The point with index `skip` is excluded
+    pub fn force_on(
+        &self,
+        skip: usize,
+        pos: Vector2D<Scalar>,
+        mass: Scalar,
+        theta: Scalar,
+        force: &impl Fn(Vector2D<Scalar>, Scalar, Vector2D<Scalar>, Scalar) -> Vector2D<Scalar>,
+    ) -> Vector2D<Scalar> {
+        self.force_on_node(0, skip, pos, mass, theta, force)
+    }
+
+    fn force_on_node(
+        &self,
+        node: usize,
+        skip: usize,
+        pos: Vector2D<Scalar>,
+        mass: Scalar,
+        theta: Scalar,
+        force: &impl Fn(Vector2D<Scalar>, Scalar, Vector2D<Scalar>, Scalar) -> Vector2D<Scalar>,
+    ) -> Vector2D<Scalar> {
+        let current = &self.nodes[node];
+        if current.mass <= 0.0 {
+            return Vector2D::new(0.0, 0.0);
+        }
+
+        match current.children {
+            None => current
+                .points
+                .iter()
+                .filter(|x| x.0 != skip)
+                .fold(Vector2D::new(0.0, 0.0), |acc, x| acc + force(pos, mass, x.1, x.2)),
+            Some(children) => {
+                let center_of_mass = current.center_of_mass();
+                let distance = (center_of_mass - pos).length();
+                if !current.contains(pos) && distance > 0.0 && (current.half_size * 2.0) / distance < theta {
+                    force(pos, mass, center_of_mass, current.mass)
+                } else {
+                    children.iter().fold(Vector2D::new(0.0, 0.0), |acc, x| {
+                        acc + self.force_on_node(*x, skip, pos, mass, theta, force)
+                    })
+                }
+            }
+        }
+    }
+}
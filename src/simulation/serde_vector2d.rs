@@ -0,0 +1,27 @@
+// vector2d doesn't implement Serialize / Deserialize, vectors are stored as [x, y] pairs instead
+
+use super::Scalar;
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use vector2d::Vector2D;
+
+pub fn serialize<S: Serializer>(val: &Vector2D<Scalar>, serializer: S) -> Result<S::Ok, S::Error> {
+    (val.x, val.y).serialize(serializer)
+}
+
+pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Vector2D<Scalar>, D::Error> {
+    let (x, y) = <(Scalar, Scalar)>::deserialize(deserializer)?;
+    Ok(Vector2D::new(x, y))
+}
+
+pub mod vec {
+    use super::*;
+
+    pub fn serialize<S: Serializer>(val: &[Vector2D<Scalar>], serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_seq(val.iter().map(|x| (x.x, x.y)))
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Vec<Vector2D<Scalar>>, D::Error> {
+        let pairs = Vec::<(Scalar, Scalar)>::deserialize(deserializer)?;
+        Ok(pairs.into_iter().map(|(x, y)| Vector2D::new(x, y)).collect())
+    }
+}
@@ -0,0 +1,48 @@
+use super::Scalar;
+use std::collections::HashMap;
+use vector2d::Vector2D;
+
+// Cells a cell is paired with besides itself, the other four neighbours pair with it from their side
+const FORWARD_NEIGHBOURS: [(i64, i64); 4] = [(1, -1), (1, 0), (1, 1), (0, 1)];
+
+// ----------------------------------------------------------------
+
+// Buckets points into square cells so that only points in the same or neighbouring cells are paired up. With the
+// cell size at least the largest distance that matters, no pair closer than that is missed
+pub struct SpatialHash {
+    cells: HashMap<(i64, i64), Vec<usize>>,
+}
+
+impl SpatialHash {
+    // Constructor
+    pub fn new(points: &[Vector2D<Scalar>], cell_size: Scalar) -> SpatialHash {
+        let mut cells: HashMap<(i64, i64), Vec<usize>> = HashMap::new();
+        if cell_size > 0.0 {
+            points.iter().enumerate().for_each(|(i, p)| {
+                let cell = ((p.x / cell_size).floor() as i64, (p.y / cell_size).floor() as i64);
+                cells.entry(cell).or_default().push(i)
+            });
+        }
+        SpatialHash { cells }
+    }
+
+    // Methods
+    // Every pair of points sharing or touching a cell once, as (lower index, higher index) in ascending order
+    pub fn candidate_pairs(&self) -> Vec<(usize, usize)> {
+        let mut out = vec![];
+        for (&(x, y), points) in &self.cells {
+            for (n, &i) in points.iter().enumerate() {
+                points[n + 1..].iter().for_each(|&j| out.push((i.min(j), i.max(j))));
+            }
+            for (dx, dy) in FORWARD_NEIGHBOURS {
+                if let Some(neighbours) = self.cells.get(&(x + dx, y + dy)) {
+                    for &i in points {
+                        neighbours.iter().for_each(|&j| out.push((i.min(j), i.max(j))));
+                    }
+                }
+            }
+        }
+        out.sort_unstable();
+        out
+    }
+}
@@ -0,0 +1,257 @@
+use super::graphics;
+use super::settings::Settings;
+use super::simulation::*;
+use super::{body_tooltip, draw_minimap, new_scene, new_simulation, update_scene};
+
+use std::collections::HashSet;
+use vector2d::Vector2D;
+use wasm_bindgen::prelude::*;
+use wasm_bindgen::{Clamped, JsCast};
+use web_sys::{CanvasRenderingContext2d, HtmlCanvasElement, ImageData};
+
+// ----------------------------------------------------------------
+
+// Browser counterpart of the minifb loop in main.rs. DOM event listeners forward input through the key_* and
+// mouse_* methods and requestAnimationFrame calls frame, keys are identified by KeyboardEvent.code
+#[wasm_bindgen]
+pub struct WebApp {
+    context: CanvasRenderingContext2d,
+    resolution: Vector2D<u32>,
+    scene: graphics::Scene,
+    simulation: Simulation,
+    physics_on: bool,
+    camera_smoothing: f32,
+
+    keys_down: HashSet<String>,
+    keys_pressed: HashSet<String>,
+    keys_repeated: HashSet<String>,
+    mouse_left_down: bool,
+    mouse_middle_down: bool,
+    mouse_screen_pos: Option<Vector2D<f32>>,
+    // Where the cursor was when the last frame was drawn
+    last_mouse_screen_pos: Option<Vector2D<f32>>,
+    mouse_scroll_wheel: Option<f32>,
+}
+
+#[wasm_bindgen]
+impl WebApp {
+    // Constructor
+    #[wasm_bindgen(constructor)]
+    pub fn new(canvas_id: &str) -> Result<WebApp, JsValue> {
+        let canvas = web_sys::window()
+            .and_then(|x| x.document())
+            .and_then(|x| x.get_element_by_id(canvas_id))
+            .ok_or_else(|| JsValue::from_str(&format!("No element with id {canvas_id:?}")))?
+            .dyn_into::<HtmlCanvasElement>()?;
+        let context = canvas
+            .get_context("2d")?
+            .ok_or_else(|| JsValue::from_str("Unable to get 2d context"))?
+            .dyn_into::<CanvasRenderingContext2d>()?;
+
+        let settings = Settings::default();
+        let resolution = Vector2D::new(canvas.width(), canvas.height());
+
+        Ok(WebApp {
+            context,
+            resolution,
+            scene: new_scene(&settings, resolution),
+            simulation: new_simulation(&settings),
+            physics_on: true,
+            camera_smoothing: settings.camera_smoothing,
+            keys_down: HashSet::new(),
+            keys_pressed: HashSet::new(),
+            keys_repeated: HashSet::new(),
+            mouse_left_down: false,
+            mouse_middle_down: false,
+            mouse_screen_pos: None,
+            last_mouse_screen_pos: None,
+            mouse_scroll_wheel: None,
+        })
+    }
+
+    // Input
+    pub fn key_down(&mut self, code: String, repeat: bool) {
+        if !repeat {
+            self.keys_pressed.insert(code.clone());
+        }
+        self.keys_repeated.insert(code.clone());
+        self.keys_down.insert(code);
+    }
+
+    pub fn key_up(&mut self, code: String) {
+        self.keys_down.remove(&code);
+    }
+
+    pub fn mouse_move(&mut self, x: f32, y: f32) {
+        self.mouse_screen_pos = Some(Vector2D::new(x, y))
+    }
+
+    pub fn mouse_leave(&mut self) {
+        self.mouse_screen_pos = None
+    }
+
+    // Buttons are numbered like MouseEvent.button, 0 is left and 1 is middle
+    pub fn mouse_button(&mut self, button: i16, down: bool) {
+        match button {
+            0 => self.mouse_left_down = down,
+            1 => self.mouse_middle_down = down,
+            _ => {}
+        }
+    }
+
+    pub fn mouse_wheel(&mut self, amount: f32) {
+        self.mouse_scroll_wheel = Some(self.mouse_scroll_wheel.unwrap_or(0.0) + amount)
+    }
+
+    // Methods
+    pub fn frame(&mut self) -> Result<(), JsValue> {
+        let mouse_world_pos = self.mouse_screen_pos.map(|x| self.scene.screen_to_world_coords(x));
+        let min_pick_radius = self.scene.min_body_radius() / self.scene.get_scale();
+        // Scrolling over a body while holding shift changes its mass instead of zooming
+        let adjust_mass = (self.is_key_down("ShiftLeft") || self.is_key_down("ShiftRight"))
+            && mouse_world_pos
+                .and_then(|x| {
+                    self.simulation
+                        .get_body_on_point_index(from_f32_vec(x), Scalar::from(min_pick_radius))
+                })
+                .is_some();
+
+        self.simulation.handle_user_input(SimulationInput {
+            add_body: self.is_key_pressed("KeyQ", true),
+            remove_body: self.is_key_pressed("KeyE", true),
+            print_body: self.is_key_pressed("KeyP", true),
+            selected_body: self.is_key_pressed("KeyV", false),
+            delete_selected: self.is_key_pressed("Delete", false),
+            duplicate_selected: self.is_key_pressed("Insert", false),
+            undo: self.is_ctrl_key_pressed("KeyZ", true),
+            redo: self.is_ctrl_key_pressed("KeyY", true),
+            up_speed: self.is_key_pressed("NumpadAdd", true),
+            down_speed: self.is_key_pressed("NumpadSubtract", true),
+            halve_time_scale: self.is_key_pressed("BracketLeft", false),
+            double_time_scale: self.is_key_pressed("BracketRight", false),
+            lower_restitution: self.is_key_pressed("Minus", true),
+            raise_restitution: self.is_key_pressed("Equal", true),
+            increase_grav_const: self.is_key_pressed("PageUp", true),
+            decrease_grav_const: self.is_key_pressed("PageDown", true),
+            flip_gravity: self.is_key_pressed("F8", false),
+            reset_contents: self.is_key_pressed("KeyR", false),
+            toggle_prediction_cone: self.is_key_pressed("KeyC", false),
+            toggle_trail_blend_mode: self.is_key_pressed("KeyB", false),
+            toggle_trail_fade: self.is_key_pressed("KeyF", false),
+            cycle_trail_length: self.is_key_pressed("KeyL", false),
+            cycle_trail_style: self.is_key_pressed("Semicolon", false),
+            clear_trails: self.is_key_pressed("KeyU", false),
+            load_figure_eight: self.is_key_pressed("Digit1", false),
+            load_orbits: self.is_key_pressed("Digit2", false),
+            toggle_fixed: self.is_key_pressed("KeyZ", false),
+            toggle_center_of_mass: self.is_key_pressed("KeyO", false),
+            toggle_force_arrows: self.is_key_pressed("F3", false),
+            cycle_color_mode: self.is_key_pressed("KeyG", false),
+            mouse_left_down: self.mouse_left_down,
+            mouse_world_pos,
+            mouse_world_delta: self
+                .mouse_screen_pos
+                .zip(self.last_mouse_screen_pos)
+                .map(|(pos, last)| (pos - last) / self.scene.get_scale()),
+            mouse_scroll_wheel: self.mouse_scroll_wheel,
+            adjust_mass,
+            min_pick_radius,
+        });
+        self.last_mouse_screen_pos = self.mouse_screen_pos;
+        self.scene.handle_user_input(graphics::SceneUserInput {
+            move_up: self.is_key_down("ArrowUp") || self.is_key_down("KeyW"),
+            move_down: self.is_key_down("ArrowDown") || self.is_key_down("KeyS"),
+            move_right: self.is_key_down("ArrowRight") || self.is_key_down("KeyD"),
+            move_left: self.is_key_down("ArrowLeft") || self.is_key_down("KeyA"),
+            zoom_in: self.is_key_down("KeyM"),
+            zoom_out: self.is_key_down("KeyN"),
+            reset_view: self.is_key_pressed("Home", false),
+            mouse_pan: self.mouse_middle_down,
+            mouse_screen_pos: self.mouse_screen_pos,
+            mouse_scroll_wheel: self.mouse_scroll_wheel.filter(|_| !adjust_mass),
+        });
+        if self.is_key_pressed("End", false) {
+            if let Some((min, max)) = self.simulation.extent() {
+                self.scene.fit_bounds(to_f32_vec(min), to_f32_vec(max))
+            }
+        }
+        if let Some(selected_body) = *self.simulation.selected_body() {
+            if let Some(body) = self.simulation.get_body(selected_body) {
+                self.scene
+                    .focus_on_smooth(to_f32_vec(*body.pos()), self.camera_smoothing)
+            }
+        }
+
+        if self.is_key_pressed("KeyY", false) {
+            let antialias = !*self.scene.antialias();
+            self.scene.set_antialias(antialias);
+        }
+        if self.is_key_pressed("F2", false) {
+            let show_grid = !*self.scene.show_grid();
+            self.scene.set_show_grid(show_grid);
+        }
+        if self.is_key_pressed("F4", false) {
+            let show_field = !*self.scene.show_field();
+            self.scene.set_show_field(show_field);
+        }
+        if self.is_key_pressed("F10", false) {
+            let show_minimap = !*self.scene.show_minimap();
+            self.scene.set_show_minimap(show_minimap);
+        }
+        if self.is_key_pressed("F11", false) {
+            let bloom = !*self.scene.bloom();
+            self.scene.set_bloom(bloom);
+        }
+        self.physics_on ^= self.is_key_pressed("Space", false);
+        let step_once = self.is_key_pressed("Period", false);
+        if self.physics_on || step_once {
+            self.simulation.physics_tick();
+        }
+
+        update_scene(&mut self.scene, &self.simulation);
+        let mut frame_buffer = self.scene.to_frame_buffer();
+        draw_minimap(&self.scene, &self.simulation, &mut frame_buffer);
+        // The view may have moved since the input was handled
+        let mouse_world_pos = self.mouse_screen_pos.map(|x| self.scene.screen_to_world_coords(x));
+        if let Some((pos, text)) = mouse_world_pos.and_then(|x| body_tooltip(&self.scene, &self.simulation, x)) {
+            frame_buffer.draw_text_panel(pos, &text, self.scene.theme().hud, *self.scene.background());
+        }
+        let pixels = frame_buffer.to_vec_u8(true);
+        let image_data =
+            ImageData::new_with_u8_clamped_array_and_sh(Clamped(&pixels), self.resolution.x, self.resolution.y)?;
+        self.context.put_image_data(&image_data, 0.0, 0.0)?;
+
+        self.keys_pressed.clear();
+        self.keys_repeated.clear();
+        self.mouse_scroll_wheel = None;
+        Ok(())
+    }
+}
+
+impl WebApp {
+    fn is_key_down(&self, code: &str) -> bool {
+        self.keys_down.contains(code)
+    }
+
+    fn is_ctrl_down(&self) -> bool {
+        self.is_key_down("ControlLeft") || self.is_key_down("ControlRight")
+    }
+
+    // Like minifb's KeyRepeat, with repeat held keys also count once per keydown event the browser repeats. Keys
+    // pressed while Ctrl is held only count for is_ctrl_key_pressed, as in the native keymap
+    fn is_key_pressed(&self, code: &str, repeat: bool) -> bool {
+        !self.is_ctrl_down() && self.is_pressed_or_repeated(code, repeat)
+    }
+
+    fn is_ctrl_key_pressed(&self, code: &str, repeat: bool) -> bool {
+        self.is_ctrl_down() && self.is_pressed_or_repeated(code, repeat)
+    }
+
+    fn is_pressed_or_repeated(&self, code: &str, repeat: bool) -> bool {
+        if repeat {
+            self.keys_repeated.contains(code)
+        } else {
+            self.keys_pressed.contains(code)
+        }
+    }
+}
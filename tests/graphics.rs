@@ -0,0 +1,219 @@
+// Integration tests for the drawing code, run with `cargo test` or `cargo test --test graphics` for only these
+use frame_buffer::graphics::{Color, Draw, FrameBuffer, Line, Polyline, Rect, Scene, SceneUserInput};
+use frame_buffer::simulation::{CollisionMode, PhysicsBody, Simulation};
+use vector2d::Vector2D;
+
+// Scenes this wide draw the world at a scale of 1
+const RES: Vector2D<u32> = Vector2D { x: 500, y: 500 };
+
+// ----------------------------------------------------------------
+
+fn brightness(frame_buffer: &FrameBuffer, x: u32, y: u32) -> f32 {
+    frame_buffer.buffer()[(y * frame_buffer.size().x + x) as usize].r as f32 / 255.0
+}
+
+fn lit(frame_buffer: &FrameBuffer) -> usize {
+    frame_buffer
+        .buffer()
+        .iter()
+        .filter(|x| **x != Color::new(0, 0, 0))
+        .count()
+}
+
+// ----------------------------------------------------------------
+
+#[test]
+fn svg_trail_is_one_polyline() {
+    let trail = Polyline::new(
+        vec![
+            Vector2D::new(0.0, 0.0),
+            Vector2D::new(10.0, 0.0),
+            Vector2D::new(10.0, 5.5),
+        ],
+        0,
+        Color::new(255, 0, 16),
+    );
+    assert_eq!(
+        trail.to_svg_element(0),
+        "<polyline points=\"0,0 10,0 10,5.5\" fill=\"none\" stroke=\"#ff0010\" stroke-width=\"1\" />"
+    );
+
+    let body = PhysicsBody::builder()
+        .pos(Vector2D::new(100.0, 100.0))
+        .velocity(Vector2D::new(2.0, 1.0))
+        .build();
+    let mut simulation = Simulation::new(vec![body], Some(0.0), None, None, CollisionMode::None);
+    for _ in 0..20 {
+        simulation.physics_tick();
+    }
+    assert!(simulation.bodies()[0].trail().len() > 2);
+    let svg = Scene::new(simulation.shapes(), RES, None).to_svg();
+    assert_eq!(svg.matches("<polyline").count(), 1);
+    // Only the velocity arrow is still a line
+    assert_eq!(svg.matches("<line").count(), 1);
+
+    // A fading trail is still one polyline, drawn with a gradient
+    simulation.set_trail_fade(true);
+    let svg = Scene::new(simulation.shapes(), RES, None).to_svg();
+    assert_eq!(svg.matches("<polyline").count(), 1);
+    assert_eq!(svg.matches("<linearGradient").count(), 1);
+}
+
+#[test]
+fn svg_gradient_ids_are_per_document() {
+    let trail = |y: f32| {
+        let mut trail = Polyline::new(
+            vec![Vector2D::new(0.0, y), Vector2D::new(10.0, y)],
+            0,
+            Color::new(255, 255, 255),
+        );
+        trail.set_fade_from(Some(Color::new(0, 0, 0)));
+        Box::new(trail) as Box<dyn Draw>
+    };
+    let scene = Scene::new(vec![trail(0.0), trail(5.0)], RES, None);
+    let svg = scene.to_svg();
+    assert!(svg.contains("id=\"fade0\"") && svg.contains("id=\"fade1\""));
+    // Exporting again gives the same document
+    assert_eq!(scene.to_svg(), svg);
+}
+
+#[test]
+fn rect_scales_both_sides() {
+    let rect = Rect::new(
+        Vector2D::new(1.0, 2.0),
+        Vector2D::new(10.0, 30.0),
+        0,
+        Color::new(255, 255, 255),
+    );
+    assert_eq!(
+        rect.scale(2.0).to_svg_element(0),
+        "<rect x=\"2\" y=\"4\" width=\"20\" height=\"60\" fill=\"#ffffff\" />"
+    );
+}
+
+#[test]
+fn blend_red_over_blue() {
+    let (red, blue) = (Color::new(255, 0, 0), Color::new(0, 0, 255));
+    assert_eq!(red.lerp(blue, 0.5), Color::new(128, 0, 128));
+    assert_eq!(red.lerp(blue, 0.0), red);
+    assert_eq!(red.lerp(blue, 1.0), blue);
+
+    let mut frame_buffer = FrameBuffer::new_filled(Vector2D::new(2, 1), blue);
+    frame_buffer.blend_pixel(Vector2D::new(0.0, 0.0), red, 0.5);
+    assert_eq!(frame_buffer.buffer()[0], Color::new(128, 0, 128));
+    // Only the pixel that was blended
+    assert_eq!(frame_buffer.buffer()[1], blue);
+    // An opaque blend is the same as set_pixel
+    frame_buffer.blend_pixel(Vector2D::new(1.0, 0.0), red, 1.0);
+    assert_eq!(frame_buffer.buffer()[1], red);
+}
+
+#[test]
+fn antialiased_line_coverage_at_45_degrees() {
+    let white = Color::new(255, 255, 255);
+    let mut line = Line::new(Vector2D::new(10.5, 10.5), Vector2D::new(40.5, 40.5), 0, white);
+    line.set_antialias(true);
+
+    // Through the middle of every pixel on the diagonal, which are covered fully and nothing else is
+    let mut frame_buffer = FrameBuffer::new(Vector2D::new(50, 50));
+    frame_buffer.draw(&line);
+    for x in 11..40 {
+        assert_eq!(brightness(&frame_buffer, x, x), 1.0);
+        assert_eq!(brightness(&frame_buffer, x, x + 1), 0.0);
+        assert_eq!(brightness(&frame_buffer, x + 1, x), 0.0);
+    }
+
+    // Half a pixel lower it runs between two pixels in every column, which share the coverage
+    line.set_pos_1(Vector2D::new(10.5, 11.0));
+    line.set_pos_2(Vector2D::new(40.5, 41.0));
+    let mut frame_buffer = FrameBuffer::new(Vector2D::new(50, 50));
+    frame_buffer.draw(&line);
+    for x in 11..40 {
+        assert!((brightness(&frame_buffer, x, x) - 0.5).abs() < 0.01);
+        assert!((brightness(&frame_buffer, x, x + 1) - 0.5).abs() < 0.01);
+        let column: f32 = (0..50).map(|y| brightness(&frame_buffer, x, y)).sum();
+        assert!((column - 1.0).abs() < 0.01);
+    }
+}
+
+#[test]
+fn off_screen_rect_touches_no_pixels() {
+    let white = Color::new(255, 255, 255);
+    let mut frame_buffer = FrameBuffer::new(Vector2D::new(100, 100));
+    for pos in [(-500.0, 20.0), (150.0, 20.0), (20.0, -500.0), (20.0, 150.0)] {
+        frame_buffer.draw(&Rect::new(
+            Vector2D::new(pos.0, pos.1),
+            Vector2D::new(400.0, 400.0),
+            0,
+            white,
+        ));
+    }
+    assert_eq!(
+        *frame_buffer.buffer(),
+        *FrameBuffer::new(Vector2D::new(100, 100)).buffer()
+    );
+
+    // Only the part that is on screen
+    frame_buffer.draw(&Rect::new(
+        Vector2D::new(-50.0, 90.0),
+        Vector2D::new(60.0, 1000.0),
+        0,
+        white,
+    ));
+    assert_eq!(lit(&frame_buffer), 10 * 10);
+}
+
+#[test]
+fn clipped_diagonal_line_matches_unclipped() {
+    let white = Color::new(255, 255, 255);
+    // Crosses the whole 100 by 100 viewport and goes far past it on both ends
+    let (start, end) = (Vector2D::new(-50.0, -20.0), Vector2D::new(150.0, 130.0));
+    let mut frame_buffer = FrameBuffer::new(Vector2D::new(100, 100));
+    frame_buffer.draw(&Line::new(start, end, 0, white));
+
+    // The same line in a framebuffer big enough to hold all of it, so nothing needs clipping
+    let offset = Vector2D::new(100.0, 100.0);
+    let mut large = FrameBuffer::new(Vector2D::new(300, 300));
+    large.draw(&Line::new(start + offset, end + offset, 0, white));
+
+    // The clipped line starts again from where it enters the screen, so it may round to the row next to the
+    // unclipped one but never further, and still covers every column once
+    let row = |frame_buffer: &FrameBuffer, x: u32, offset: u32| -> Vec<u32> {
+        (0..100)
+            .filter(|y| brightness(frame_buffer, x + offset, y + offset) > 0.0)
+            .collect()
+    };
+    for x in 0..100 {
+        let (clipped, unclipped) = (row(&frame_buffer, x, 0), row(&large, x, 100));
+        assert_eq!(clipped.len(), 1, "column {x}");
+        assert_eq!(unclipped.len(), 1, "column {x}");
+        assert!(clipped[0].abs_diff(unclipped[0]) <= 1, "column {x}");
+    }
+}
+
+#[test]
+fn screen_world_round_trip() {
+    let mut scene = Scene::new(vec![], Vector2D::new(1000, 600), None);
+    scene.set_offset(Vector2D::new(-120.0, 35.5));
+    scene.set_scale(1.75);
+    let close = |a: Vector2D<f32>, b: Vector2D<f32>| (a - b).length() < 1e-3;
+
+    let screen = Vector2D::new(321.0, 123.0);
+    let world = scene.screen_to_world_coords(screen);
+    assert!(close(scene.world_to_screen_coords(world), screen));
+    let world = Vector2D::new(-40.0, 900.0);
+    assert!(close(
+        scene.screen_to_world_coords(scene.world_to_screen_coords(world)),
+        world
+    ));
+
+    // Scrolling zooms on the pixel under the cursor, so the same part of the world stays there
+    let under_cursor = scene.screen_to_world_coords(screen);
+    scene.handle_user_input(SceneUserInput {
+        mouse_screen_pos: Some(screen),
+        mouse_scroll_wheel: Some(0.5),
+        ..Default::default()
+    });
+    assert_eq!(scene.get_scale(), 2.0 * 2.25);
+    assert!(close(scene.world_to_screen_coords(under_cursor), screen));
+}
@@ -0,0 +1,830 @@
+// Integration tests for the physics core, run with `cargo test` or `cargo test --test physics` for only these
+use frame_buffer::graphics::Color;
+use frame_buffer::simulation::{
+    to_f32_vec, BoundaryMode, Bounds, CollisionMode, CollisionVisuals, Force, GravityMode, Integrator, PhysicsBody,
+    Scalar, Simulation, SimulationInput, SimulationState, Velocity,
+};
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+use vector2d::Vector2D;
+
+const SEED: u64 = 42;
+
+// ----------------------------------------------------------------
+
+fn seeded_bodies(num_of_bodies: usize) -> Vec<PhysicsBody> {
+    let mut rng = StdRng::seed_from_u64(SEED);
+    (0..num_of_bodies)
+        .map(|_| PhysicsBody::new_rand_with(&mut rng))
+        .collect()
+}
+
+fn momentum(simulation: &Simulation) -> Vector2D<Scalar> {
+    simulation
+        .bodies()
+        .iter()
+        .fold(Vector2D::new(0.0, 0.0), |acc, x| acc + x.velocity().0 * *x.mass())
+}
+
+fn body(pos: [Scalar; 2], velocity: [Scalar; 2], mass: Scalar) -> PhysicsBody {
+    PhysicsBody::builder()
+        .pos(Vector2D::new(pos[0], pos[1]))
+        .velocity(Vector2D::new(velocity[0], velocity[1]))
+        .mass(mass)
+        .build()
+}
+
+// A light body 100 from a heavy one at the speed of a circular orbit, which takes about 628 ticks
+fn circular_orbit() -> Simulation {
+    let (central_mass, radius) = (1000.0, 100.0);
+    let speed = (0.1 * central_mass / radius as Scalar).sqrt();
+    let bodies = vec![
+        body([0.0, 0.0], [0.0, 0.0], central_mass),
+        body([radius, 0.0], [0.0, speed], 1.0),
+    ];
+    Simulation::new(bodies, Some(0.1), None, None, CollisionMode::None)
+}
+
+fn assert_close(a: Vector2D<Scalar>, b: Vector2D<Scalar>, tolerance: Scalar) {
+    assert!(
+        (a - b).length() <= tolerance,
+        "{a:?} is not within {tolerance} of {b:?}"
+    );
+}
+
+// ----------------------------------------------------------------
+
+#[test]
+fn two_body_momentum_is_conserved() {
+    let mut bodies = seeded_bodies(2);
+    bodies[0].set_pos(Vector2D::new(100.0, 250.0));
+    bodies[1].set_pos(Vector2D::new(400.0, 250.0));
+    let mut simulation = Simulation::new(bodies, None, None, None, CollisionMode::None);
+
+    let before = momentum(&simulation);
+    let scale: Scalar = simulation
+        .bodies()
+        .iter()
+        .map(|x| x.velocity().speed() * x.mass())
+        .sum();
+    for _ in 0..5000 {
+        simulation.physics_tick();
+    }
+    assert_close(momentum(&simulation), before, scale * 1e-3);
+}
+
+// A light body on a circular orbit around a heavy one for many periods, the velocity Verlet integrator keeps the
+// energy bounded so the drift that is left is mostly rounding. Run with `--features f64` for the f64 bound
+#[test]
+fn long_orbit_energy_drift() {
+    let mut simulation = circular_orbit();
+    simulation.set_integrator(Integrator::VelocityVerlet);
+
+    let before = simulation.total_energy();
+    for _ in 0..100_000 {
+        simulation.physics_tick();
+    }
+    let drift = ((simulation.total_energy() - before) / before).abs();
+    // Around 1e-5 in f32 and 1e-7 in f64
+    let tolerance = if cfg!(feature = "f64") { 1e-6 } else { 1e-4 };
+    assert!(
+        drift < tolerance,
+        "relative energy drift {drift:e} is over {tolerance:e}"
+    );
+}
+
+#[test]
+fn remove_body_keeps_selected_body() {
+    let select = |simulation: &mut Simulation, i: usize| {
+        let pos = *simulation.bodies()[i].pos();
+        simulation.handle_user_input(SimulationInput {
+            selected_body: true,
+            mouse_world_pos: Some(to_f32_vec(pos)),
+            ..Default::default()
+        });
+        assert_eq!(*simulation.selected_body(), Some(i));
+    };
+    let mut bodies = seeded_bodies(5);
+    bodies
+        .iter_mut()
+        .enumerate()
+        .for_each(|(i, x)| x.set_pos(Vector2D::new(i as Scalar * 200.0, 0.0)));
+    let mut simulation = Simulation::new(bodies, None, None, None, CollisionMode::None);
+
+    // Removing a body before the selected one moves the selection down with it
+    select(&mut simulation, 3);
+    let selected = simulation.bodies()[3].clone();
+    simulation.remove_body(1);
+    assert_eq!(*simulation.selected_body(), Some(2));
+    assert_eq!(simulation.bodies()[2], selected);
+
+    // Removing a body after it changes nothing
+    simulation.remove_body(3);
+    assert_eq!(*simulation.selected_body(), Some(2));
+
+    // Removing the selected body clears the selection
+    simulation.remove_body(2);
+    assert_eq!(*simulation.selected_body(), None);
+
+    // Out of range indices are ignored
+    select(&mut simulation, 1);
+    simulation.remove_body(10);
+    assert_eq!(simulation.bodies().len(), 2);
+    assert_eq!(*simulation.selected_body(), Some(1));
+}
+
+#[test]
+fn intersects_and_distance_between() {
+    let a = body([0.0, 0.0], [0.0, 0.0], 25.0);
+    let b = body([3.0, 4.0], [0.0, 0.0], 25.0);
+    assert_eq!(a.distance_between(&b), 5.0);
+    assert_eq!(b.distance_between(&a), 5.0);
+    assert_eq!(a.distance_between(&a), 0.0);
+
+    // Bodies intersect when the distance between their centers is below the sum of their radii
+    let radii = *a.radius() + *b.radius();
+    assert!(a.intersects(&b) == (5.0 < radii));
+    let far = body([radii * 2.0, 0.0], [0.0, 0.0], 25.0);
+    assert!(!a.intersects(&far));
+    assert!(!far.intersects(&a));
+    let touching = body([*a.radius() * 2.0, 0.0], [0.0, 0.0], 25.0);
+    assert!(!a.intersects(&touching));
+    let overlapping = body([*a.radius() * 2.0 - 0.01, 0.0], [0.0, 0.0], 25.0);
+    assert!(a.intersects(&overlapping));
+    assert!(overlapping.intersects(&a));
+}
+
+#[test]
+fn force_arithmetic() {
+    let a = Force::from_vector2d(Vector2D::new(3.0, 4.0));
+    assert_eq!(*a.amplitude(), 5.0);
+    assert_close(*a.direction(), Vector2D::new(0.6, 0.8), 1e-6);
+    assert_close(a.as_vector2d(), Vector2D::new(3.0, 4.0), 1e-5);
+
+    let b = Force::new(Vector2D::new(-2.0, 0.0), 1.0);
+    assert_close((a + b).as_vector2d(), Vector2D::new(2.0, 4.0), 1e-5);
+    let mut c = a;
+    c += b;
+    assert_eq!(c, a + b);
+
+    assert_close((a * 2.0).as_vector2d(), Vector2D::new(6.0, 8.0), 1e-5);
+    assert_eq!(*(a * 2.0).amplitude(), 10.0);
+    assert_close((-a).as_vector2d(), Vector2D::new(-3.0, -4.0), 1e-5);
+    assert_close((a + -a).as_vector2d(), Vector2D::new(0.0, 0.0), 1e-5);
+
+    // Negative amplitudes are stored as their absolute value
+    assert_eq!(*Force::new(Vector2D::new(1.0, 0.0), -2.0).amplitude(), 2.0);
+
+    // Random forces are the same for the same seed
+    let mut rng_1 = StdRng::seed_from_u64(SEED);
+    let mut rng_2 = StdRng::seed_from_u64(SEED);
+    assert_eq!(Force::new_rand_with(&mut rng_1), Force::new_rand_with(&mut rng_2));
+    assert_eq!(Velocity::new_rand_with(&mut rng_1), Velocity::new_rand_with(&mut rng_2));
+}
+
+#[test]
+fn force_addition() {
+    let right = Force::new(Vector2D::new(1.0, 0.0), 1.0);
+    let up = Force::new(Vector2D::new(0.0, 1.0), 1.0);
+    assert!(*(right + -right).amplitude() < 1e-6);
+    assert!((*(right + up).amplitude() - (2.0 as Scalar).sqrt()).abs() < 1e-6);
+    assert_close(
+        *(right + up).direction(),
+        Vector2D::<Scalar>::new(1.0, 1.0).normalise(),
+        1e-6,
+    );
+    // Large sums aren't clamped
+    assert_eq!(*(right * 100.0 + right * 100.0).amplitude(), 200.0);
+}
+
+#[test]
+fn simulation_extent() {
+    let bodies = vec![
+        body([-100.0, 20.0], [0.0, 0.0], 25.0),
+        body([50.0, -80.0], [0.0, 0.0], 100.0),
+        body([10.0, 300.0], [0.0, 0.0], 5.0),
+    ];
+    let radii: Vec<Scalar> = bodies.iter().map(|x| *x.radius()).collect();
+    let simulation = Simulation::new(bodies, None, None, None, CollisionMode::None);
+
+    // The corners are the outermost edges of the bodies, not their centers
+    let (min, max) = simulation.extent().unwrap();
+    assert_eq!(min, Vector2D::new(-100.0 - radii[0], -80.0 - radii[1]));
+    assert_eq!(max, Vector2D::new(50.0 + radii[1], 300.0 + radii[2]));
+
+    let empty = Simulation::new(vec![], None, None, None, CollisionMode::None);
+    assert!(empty.extent().is_none());
+}
+
+#[test]
+fn zero_physics_speed_pauses() {
+    let mut simulation = Simulation::new(seeded_bodies(5), None, None, None, CollisionMode::None);
+    simulation.set_physics_speed(0);
+    assert_eq!(*simulation.physics_speed(), 0);
+
+    let before = simulation.bodies().clone();
+    for _ in 0..10 {
+        simulation.physics_tick();
+    }
+    assert_eq!(*simulation.bodies(), before);
+
+    // Speeding up from 0 resumes
+    simulation.handle_user_input(SimulationInput {
+        up_speed: true,
+        ..Default::default()
+    });
+    simulation.physics_tick();
+    assert_ne!(*simulation.bodies(), before);
+}
+
+// Substeps split every tick into smaller steps, the same ticks then cover the same time with less integration error
+#[test]
+fn substeps_reduce_energy_drift() {
+    let run = |substeps: u32| {
+        let mut simulation = circular_orbit();
+        simulation.set_integration_substeps(substeps);
+        let before = simulation.total_energy();
+        for _ in 0..2000 {
+            simulation.physics_tick();
+        }
+        let drift = ((simulation.total_energy() - before) / before).abs();
+        (drift, *simulation.bodies()[1].pos())
+    };
+    let (drift_1, pos_1) = run(1);
+    let (drift_8, pos_8) = run(8);
+    assert!(drift_8 < drift_1 / 4.0, "{drift_8:e} is not much below {drift_1:e}");
+    // The same number of ticks still covers the same time, so the body is at about the same point of its orbit
+    assert_close(pos_8, pos_1, 2.0);
+}
+
+#[test]
+fn closest_pair_finds_nearest_bodies() {
+    let simulation = Simulation::new(
+        vec![
+            body([0.0, 0.0], [0.0, 0.0], 1.0),
+            body([100.0, 0.0], [0.0, 0.0], 1.0),
+            body([50.0, 50.0], [0.0, 0.0], 1.0),
+            body([103.0, 4.0], [0.0, 0.0], 1.0),
+            body([0.0, 200.0], [0.0, 0.0], 1.0),
+        ],
+        None,
+        None,
+        None,
+        CollisionMode::None,
+    );
+    let (i, j, distance) = simulation.closest_pair().unwrap();
+    assert_eq!((i, j), (1, 3));
+    assert!((distance - 5.0).abs() < 1e-4);
+
+    let single = Simulation::new(
+        vec![body([0.0, 0.0], [0.0, 0.0], 1.0)],
+        None,
+        None,
+        None,
+        CollisionMode::None,
+    );
+    assert!(single.closest_pair().is_none());
+    let empty = Simulation::new(vec![], None, None, None, CollisionMode::None);
+    assert!(empty.closest_pair().is_none());
+}
+
+#[test]
+fn collision_visuals() {
+    let visuals = CollisionVisuals {
+        flash_color: Color::new(255, 0, 0),
+        flash_intensity: 1.0,
+        debris_color: Some(Color::new(0, 255, 0)),
+    };
+    let mut simulation = Simulation::new(
+        vec![body([0.0, 0.0], [2.0, 0.0], 30.0), body([10.0, 0.0], [-2.0, 0.0], 30.0)],
+        Some(0.0),
+        None,
+        None,
+        CollisionMode::Shatter,
+    );
+    simulation.set_collision_visuals(visuals);
+    simulation.gravity_matrix_mut().set_interacts(1, 2, false);
+
+    // Saved with the scene, along with which groups attract each other
+    let json = serde_json::to_string(&simulation.state(false)).unwrap();
+    let state: SimulationState = serde_json::from_str(&json).unwrap();
+    assert_eq!(state.collision_visuals, visuals);
+    assert_eq!(Simulation::from_state(state).collision_visuals(), &visuals);
+    let path = std::env::temp_dir().join("frame_buffer_collision_visuals.json");
+    simulation.save_to_path(&path, false).unwrap();
+    let loaded = Simulation::load_from_path(&path).unwrap();
+    std::fs::remove_file(&path).unwrap();
+    assert_eq!(loaded.collision_visuals(), &visuals);
+    assert!(!loaded.gravity_matrix().interacts(2, 1));
+    assert_eq!(loaded.gravity_matrix(), simulation.gravity_matrix());
+
+    // Fragments take the debris color and the collision leaves a flash
+    let shapes_before = simulation.shapes().len();
+    simulation.physics_tick();
+    assert!(simulation.bodies().len() > 2);
+    assert!(simulation.bodies().iter().all(|x| *x.color() == Color::new(0, 255, 0)));
+    let mut without_flashes = simulation.clone();
+    without_flashes.set_collision_visuals(CollisionVisuals {
+        flash_intensity: 0.0,
+        ..visuals
+    });
+    assert_eq!(simulation.shapes().len(), without_flashes.shapes().len() + 1);
+    assert!(simulation.shapes().len() > shapes_before);
+}
+
+// Each pair's force is applied to both bodies, equal and opposite, so gravity alone never changes the total momentum
+#[test]
+fn gravity_tick_conserves_momentum() {
+    let mut simulation = Simulation::new(seeded_bodies(6), None, None, None, CollisionMode::None);
+    let scale: Scalar = simulation
+        .bodies()
+        .iter()
+        .map(|x| x.velocity().speed() * x.mass())
+        .sum();
+    for _ in 0..100 {
+        let before = momentum(&simulation);
+        simulation.gravity_tick(1.0);
+        assert_close(momentum(&simulation), before, scale * 1e-5);
+        simulation.movement_tick(1.0);
+    }
+}
+
+// Halving dt and taking twice the steps follows the same orbit, only the error in it changes
+#[test]
+fn half_dt_matches_full_dt() {
+    let mut full = circular_orbit();
+    full.set_integrator(Integrator::VelocityVerlet);
+    let mut half = full.clone();
+
+    // Most of an orbit, the two end up around 0.02 apart
+    for _ in 0..500 {
+        full.physics_tick_dt(1.0);
+        half.physics_tick_dt(0.5);
+        half.physics_tick_dt(0.5);
+    }
+    assert_close(*half.bodies()[1].pos(), *full.bodies()[1].pos(), 0.1);
+    assert_close(half.bodies()[1].velocity().0, full.bodies()[1].velocity().0, 0.01);
+}
+
+// Equal masses meeting head on swap velocities, the overlap is pushed apart so they don't collide again next tick
+#[test]
+fn head_on_collision_conserves_momentum() {
+    let mut simulation = Simulation::new(
+        vec![body([0.0, 0.0], [1.5, 0.0], 10.0), body([3.5, 0.0], [-0.5, 0.0], 10.0)],
+        Some(0.0),
+        None,
+        None,
+        CollisionMode::Collide,
+    );
+
+    let before = momentum(&simulation);
+    assert_eq!(simulation.collision_tick().len(), 1);
+    assert_close(momentum(&simulation), before, 1e-4);
+    assert_close(simulation.bodies()[0].velocity().0, Vector2D::new(-0.5, 0.0), 1e-4);
+    assert_close(simulation.bodies()[1].velocity().0, Vector2D::new(1.5, 0.0), 1e-4);
+    assert!(!simulation.bodies()[0].intersects(&simulation.bodies()[1]));
+}
+
+#[test]
+fn absorb_conserves_mass_and_momentum() {
+    let mut simulation = Simulation::new(
+        vec![body([0.0, 0.0], [1.0, 1.0], 10.0), body([3.0, 0.0], [-0.5, 1.0], 30.0)],
+        Some(0.0),
+        None,
+        None,
+        CollisionMode::Absorb,
+    );
+    let before = momentum(&simulation);
+    simulation.collision_tick();
+    assert_eq!(simulation.bodies().len(), 1);
+    let merged = &simulation.bodies()[0];
+    assert_eq!(*merged.mass(), 40.0);
+    assert_eq!(*merged.radius(), PhysicsBody::radius_from_mass(40.0));
+    // Mass-weighted centroid
+    assert_close(*merged.pos(), Vector2D::new(2.25, 0.0), 1e-4);
+    assert_close(momentum(&simulation), before, 1e-4);
+
+    // Three overlapping bodies become one without counting any twice, the selected body past them is still selected
+    let mut simulation = Simulation::new(
+        vec![
+            body([0.0, 0.0], [1.0, 1.0], 10.0),
+            body([100.0, 0.0], [0.0, 1.0], 5.0),
+            body([200.0, 0.0], [0.0, 1.0], 5.0),
+            body([3.0, 0.0], [0.5, 1.0], 30.0),
+            body([4.0, 0.0], [0.2, 1.0], 5.0),
+        ],
+        Some(0.0),
+        None,
+        None,
+        CollisionMode::Absorb,
+    );
+    simulation.handle_user_input(SimulationInput {
+        selected_body: true,
+        mouse_world_pos: Some(Vector2D::new(200.0, 0.0)),
+        ..Default::default()
+    });
+    assert_eq!(*simulation.selected_body(), Some(2));
+    let before = momentum(&simulation);
+    simulation.collision_tick();
+    assert_eq!(simulation.bodies().len(), 3);
+    assert_eq!(*simulation.bodies()[0].mass(), 45.0);
+    assert_close(momentum(&simulation), before, 1e-3);
+    assert_eq!(*simulation.selected_body(), Some(2));
+    assert_eq!(*simulation.bodies()[2].pos(), Vector2D::new(200.0, 0.0));
+}
+
+// A body's acceleration depends on the masses pulling on it and not on its own mass, while the same force moves a
+// heavier body less
+#[test]
+fn acceleration_is_independent_of_own_mass() {
+    let acceleration = |mass: Scalar| {
+        let mut simulation = Simulation::new(
+            vec![body([0.0, 0.0], [0.0, 0.0], 100.0), body([50.0, 0.0], [0.0, 0.0], mass)],
+            Some(0.1),
+            None,
+            None,
+            CollisionMode::None,
+        );
+        simulation.gravity_tick(1.0);
+        (simulation.bodies()[0].velocity().0, simulation.bodies()[1].velocity().0)
+    };
+
+    let (pull_on_light, light) = acceleration(1.0);
+    let (pull_on_heavy, heavy) = acceleration(20.0);
+    assert!(light.length() > 0.0);
+    assert_close(heavy, light, 1e-6);
+    // Between the pair the forces are equal, so the central body accelerates 20 times as fast towards the heavy one
+    assert_close(pull_on_heavy, pull_on_light * 20.0, 1e-6);
+}
+
+#[test]
+fn acceleration_is_clamped() {
+    // About 0.1 * 10000 / 2^2 = 250 unclamped, MAX_ACCELERATION caps it at 10
+    let mut simulation = Simulation::new(
+        vec![
+            body([0.0, 0.0], [0.0, 0.0], 10_000.0),
+            body([2.0, 0.0], [0.0, 0.0], 1.0),
+        ],
+        Some(0.1),
+        None,
+        None,
+        CollisionMode::None,
+    );
+    simulation.gravity_tick(1.0);
+    let speed = simulation.bodies()[1].velocity().speed();
+    assert!((speed - 10.0).abs() < 1e-4, "clamped acceleration is {speed}");
+}
+
+// theta 0 opens every node so it is exact. With 0.5 a body whose pulls nearly cancel can be off by more, so that is
+// compared over all the bodies, around 0.5% off
+#[test]
+fn barnes_hut_matches_direct() {
+    let simulation = Simulation::new(seeded_bodies(300), None, None, None, CollisionMode::None);
+    let direct = simulation.direct_forces();
+    let exact = simulation.barnes_hut_forces(0.0);
+    let approximate = simulation.barnes_hut_forces(0.5);
+    for i in 0..direct.len() {
+        assert_close(exact[i], direct[i], 1e-3 * direct[i].length().max(1e-3));
+    }
+    let error: Scalar = (0..direct.len()).map(|i| (approximate[i] - direct[i]).length()).sum();
+    let total: Scalar = direct.iter().map(|x| x.length()).sum();
+    assert!(error < 0.02 * total, "relative error {}", error / total);
+}
+
+#[test]
+fn verlet_orbit_stays_circular() {
+    let mut simulation = circular_orbit();
+    simulation.set_integrator(Integrator::VelocityVerlet);
+    for _ in 0..5000 {
+        simulation.physics_tick();
+        let radius = simulation.bodies()[0].distance_between(&simulation.bodies()[1]);
+        assert!((radius - 100.0).abs() < 2.0, "orbital radius drifted to {radius}");
+    }
+}
+
+// The Plummer force is strongest at a distance of softening / sqrt(2), where it is 2 / (3 * sqrt(3)) times
+// grav_const * mass1 * mass2 / softening^2, and falls back to zero as the bodies meet
+#[test]
+fn softening_bounds_force() {
+    let mut simulation = Simulation::new(
+        vec![body([0.0, 0.0], [0.0, 0.0], 10.0), body([1e-4, 0.0], [0.0, 0.0], 10.0)],
+        Some(0.1),
+        None,
+        None,
+        CollisionMode::None,
+    );
+    simulation.set_softening(0.5);
+    let bound = 2.0 / (3.0 * (3.0 as Scalar).sqrt()) * 0.1 * 10.0 * 10.0 / 0.25;
+    for i in 0..=100 {
+        simulation.bodies_mut()[1].set_pos(Vector2D::new(i as Scalar * 0.02 + 1e-4, 0.0));
+        let force = simulation.direct_forces()[0].length();
+        assert!(force.is_finite() && force <= bound * 1.0001, "{force} is over {bound}");
+    }
+
+    // Without softening bodies on top of each other don't pull at all rather than infinitely
+    simulation.set_softening(0.0);
+    simulation.bodies_mut()[1].set_pos(Vector2D::new(0.0, 0.0));
+    assert_eq!(simulation.direct_forces()[0], Vector2D::new(0.0, 0.0));
+}
+
+// Across the seam the bodies are 2 apart instead of 98, so with wrapping they pull each other over it
+#[test]
+fn wrap_attracts_across_seam() {
+    let mut simulation = Simulation::new(
+        vec![body([1.0, 50.0], [0.0, 0.0], 1.0), body([99.0, 50.0], [0.0, 0.0], 1.0)],
+        None,
+        None,
+        None,
+        CollisionMode::None,
+    );
+    simulation.set_bounds(Some(Bounds::new(Vector2D::new(0.0, 0.0), Vector2D::new(100.0, 100.0))));
+    simulation.set_boundary_mode(BoundaryMode::Wrap);
+
+    simulation.gravity_tick(1.0);
+    assert!(simulation.bodies()[0].velocity().0.x < 0.0);
+    assert!(simulation.bodies()[1].velocity().0.x > 0.0);
+    // Bounced walls don't wrap, the same bodies pull each other in across the middle and far more weakly
+    let wrapped = simulation.direct_forces();
+    simulation.set_boundary_mode(BoundaryMode::Bounce);
+    let unwrapped = simulation.direct_forces();
+    assert!(unwrapped[0].x > 0.0 && unwrapped[1].x < 0.0);
+    assert!(unwrapped[0].length() < wrapped[0].length());
+}
+
+#[test]
+fn two_body_energy_with_verlet() {
+    let mut simulation = Simulation::new(
+        vec![
+            body([0.0, 0.0], [0.0, 1.0], 10.0),
+            body([100.0, 0.0], [0.0, -2.0], 20.0),
+        ],
+        Some(0.1),
+        None,
+        None,
+        CollisionMode::None,
+    );
+    simulation.set_softening(0.0);
+    assert!((simulation.total_kinetic_energy() - (0.5 * 10.0 * 1.0 + 0.5 * 20.0 * 4.0)).abs() < 1e-4);
+    assert!((simulation.total_potential_energy() - -0.1 * 10.0 * 20.0 / 100.0).abs() < 1e-6);
+    assert_close(simulation.total_momentum(), Vector2D::new(0.0, -30.0), 1e-4);
+
+    let mut simulation = circular_orbit();
+    simulation.set_integrator(Integrator::VelocityVerlet);
+    let before = simulation.total_energy();
+    for _ in 0..5000 {
+        simulation.physics_tick();
+        let drift = ((simulation.total_energy() - before) / before).abs();
+        assert!(drift < 1e-3, "relative energy drift {drift:e}");
+    }
+}
+
+#[test]
+fn set_mass_updates_radius() {
+    let mut body = PhysicsBody::builder().mass(10.0).build();
+    assert_eq!(*body.radius(), 2.0);
+    body.set_mass(100.0);
+    assert_eq!(*body.mass(), 100.0);
+    assert_eq!(*body.radius(), 20.0);
+    assert_eq!(*body.radius(), PhysicsBody::radius_from_mass(100.0));
+}
+
+// Without gravity a body moves by exactly its velocity times dt, whatever its mass
+#[test]
+fn movement_is_velocity_times_dt() {
+    for mass in [1.0, 50.0] {
+        let mut simulation = Simulation::new(
+            vec![body([10.0, 20.0], [1.5, -0.25], mass)],
+            Some(0.0),
+            None,
+            None,
+            CollisionMode::None,
+        );
+        simulation.physics_tick_dt(2.0);
+        assert_eq!(*simulation.bodies()[0].pos(), Vector2D::new(13.0, 19.5));
+        simulation.physics_tick_dt(0.5);
+        assert_eq!(*simulation.bodies()[0].pos(), Vector2D::new(13.75, 19.375));
+        assert_eq!(simulation.bodies()[0].velocity().0, Vector2D::new(1.5, -0.25));
+    }
+}
+
+// Each body's momentum changes, the pair's doesn't
+#[test]
+fn pair_momentum_unchanged_after_one_tick() {
+    let mut simulation = Simulation::new(
+        vec![
+            body([0.0, 0.0], [0.0, 0.5], 10.0),
+            body([30.0, 0.0], [0.0, -0.25], 40.0),
+        ],
+        Some(1.0),
+        None,
+        None,
+        CollisionMode::None,
+    );
+    let before = simulation.total_momentum();
+    let first_before = simulation.bodies()[0].velocity().0 * *simulation.bodies()[0].mass();
+    simulation.physics_tick();
+    let first_after = simulation.bodies()[0].velocity().0 * *simulation.bodies()[0].mass();
+    assert!((first_after - first_before).length() > 0.1);
+    assert_close(simulation.total_momentum(), before, 1e-5);
+}
+
+#[test]
+fn preset_orbits_stay_circular() {
+    let mut simulation = Simulation::preset_orbits(5);
+    assert_eq!(simulation.bodies().len(), 6);
+    let center_color = *simulation.bodies()[0].color();
+    assert!(simulation.bodies()[1..].iter().all(|x| *x.color() != center_color));
+
+    let distance = |simulation: &Simulation, i: usize| simulation.bodies()[0].distance_between(&simulation.bodies()[i]);
+    let start: Vec<Scalar> = (1..6).map(|i| distance(&simulation, i)).collect();
+    // One period of the outermost orbit, the inner ones go round more than once. The light bodies still pull on each
+    // other a little, over this long the middle ones drift by up to around 3%
+    let outer = &simulation.bodies()[5];
+    let period = std::f64::consts::TAU as Scalar * start[4] / outer.velocity().speed();
+    for _ in 0..period.ceil() as usize {
+        simulation.physics_tick();
+        for i in 1..6 {
+            let radius = distance(&simulation, i);
+            assert!(
+                (radius - start[i - 1]).abs() < 0.05 * start[i - 1],
+                "body {i} moved from {} to {radius}",
+                start[i - 1]
+            );
+        }
+    }
+}
+
+#[test]
+fn perturb_skips_fixed_bodies() {
+    let mut bodies = seeded_bodies(3);
+    bodies[1].set_fixed(true);
+    let mut simulation = Simulation::new(bodies, None, None, None, CollisionMode::None);
+    let mut all_free = simulation.clone();
+    all_free.bodies_mut()[1].set_fixed(false);
+    let before = simulation.bodies().clone();
+
+    simulation.perturb(1.0, &mut StdRng::seed_from_u64(SEED));
+    all_free.perturb(1.0, &mut StdRng::seed_from_u64(SEED));
+    assert_eq!(simulation.bodies()[1].velocity().0, Vector2D::new(0.0, 0.0));
+    // The other bodies get the same kicks as when nothing is fixed
+    for i in [0, 2] {
+        assert_ne!(simulation.bodies()[i].velocity(), before[i].velocity());
+        assert_eq!(simulation.bodies()[i].velocity(), all_free.bodies()[i].velocity());
+    }
+}
+
+// Resetting and the orbits preset follow the body count and seed the simulation was started with
+#[test]
+fn reset_uses_settings() {
+    let settings = frame_buffer::settings::Settings {
+        num_of_bodies: 4,
+        seed: Some(SEED),
+        ..Default::default()
+    };
+    let mut simulation = frame_buffer::new_simulation(&settings);
+    let start = simulation.bodies().clone();
+    assert_eq!(start.len(), 4);
+    assert_eq!(start, *frame_buffer::new_simulation(&settings).bodies());
+
+    simulation.handle_user_input(SimulationInput {
+        load_orbits: true,
+        ..Default::default()
+    });
+    // The central body and one on each orbit
+    assert_eq!(simulation.bodies().len(), 5);
+    simulation.handle_user_input(SimulationInput {
+        reset_contents: true,
+        ..Default::default()
+    });
+    assert_eq!(*simulation.bodies(), start);
+}
+
+#[test]
+fn negative_grav_const_repels() {
+    for integrator in [Integrator::Euler, Integrator::VelocityVerlet] {
+        let mut simulation = Simulation::new(
+            vec![body([0.0, 0.0], [0.0, 0.0], 20.0), body([10.0, 0.0], [0.0, 0.0], 20.0)],
+            Some(-0.5),
+            None,
+            None,
+            CollisionMode::None,
+        );
+        simulation.set_integrator(integrator);
+        let separation =
+            |simulation: &Simulation| (*simulation.bodies()[1].pos() - *simulation.bodies()[0].pos()).length();
+
+        let mut last = separation(&simulation);
+        for _ in 0..500 {
+            simulation.physics_tick();
+            let now = separation(&simulation);
+            assert!(now > last, "{integrator:?}: {now} after {last}");
+            last = now;
+        }
+        assert!(last.is_finite());
+    }
+}
+
+#[test]
+fn undo_restores_both_deleted_bodies() {
+    let bodies = vec![
+        body([0.0, 0.0], [0.0, 0.0], 20.0),
+        body([1.0, 0.0], [0.0, 0.0], 20.0),
+        body([500.0, 0.0], [0.0, 0.0], 20.0),
+    ];
+    let mut simulation = Simulation::new(bodies.clone(), Some(0.0), None, None, CollisionMode::Delete);
+    simulation.collision_tick();
+    assert_eq!(simulation.bodies().len(), 1);
+
+    simulation.undo();
+    assert_eq!(*simulation.bodies(), bodies);
+    simulation.redo();
+    assert_eq!(simulation.bodies().len(), 1);
+}
+
+#[test]
+fn stepping_a_clone_leaves_the_original() {
+    let mut simulation = Simulation::new(seeded_bodies(10), None, None, None, CollisionMode::Collide);
+    let before = simulation.bodies().clone();
+    let mut snapshot = simulation.clone();
+    for _ in 0..50 {
+        snapshot.physics_tick();
+    }
+    assert_ne!(*snapshot.bodies(), before);
+    assert_eq!(*simulation.bodies(), before);
+
+    // The original steps the same way the clone did
+    for _ in 0..50 {
+        simulation.physics_tick();
+    }
+    assert_eq!(simulation.bodies(), snapshot.bodies());
+}
+
+#[test]
+fn spatial_hash_matches_brute_force() {
+    let mut rng = StdRng::seed_from_u64(SEED);
+    for _ in 0..20 {
+        // Crowded enough that many bodies touch, with a few large ones so the cells are much wider than most bodies
+        let bodies: Vec<PhysicsBody> = (0..150)
+            .map(|_| {
+                let max_mass = if rng.gen_bool(0.05) { 2000.0 } else { 50.0 };
+                let pos = [rng.gen_range(-200.0..200.0), rng.gen_range(-200.0..200.0)];
+                body(pos, [0.0, 0.0], rng.gen_range(1.0..max_mass))
+            })
+            .collect();
+        let brute_force: Vec<(usize, usize)> = (0..bodies.len())
+            .flat_map(|i| (i + 1..bodies.len()).map(move |j| (i, j)))
+            .filter(|(i, j)| bodies[*i].intersects(&bodies[*j]))
+            .collect();
+
+        let simulation = Simulation::new(bodies, None, None, None, CollisionMode::Collide);
+        assert!(!brute_force.is_empty());
+        assert_eq!(simulation.colliding_pairs(), brute_force);
+    }
+}
+
+// A massless test particle feels no force, so it moves in a straight line without changing the other bodies
+#[test]
+fn massless_particle_with_verlet() {
+    let heavy = body([0.0, 0.0], [0.5, 0.0], 1000.0);
+    let particle = body([100.0, 0.0], [0.0, 1.0], 0.0);
+    let mut simulation = Simulation::new(vec![heavy.clone(), particle], None, None, None, CollisionMode::None);
+    let mut alone = Simulation::new(vec![heavy], None, None, None, CollisionMode::None);
+    simulation.set_integrator(Integrator::VelocityVerlet);
+    alone.set_integrator(Integrator::VelocityVerlet);
+
+    for _ in 0..100 {
+        simulation.physics_tick();
+        alone.physics_tick();
+    }
+    assert_eq!(simulation.bodies()[0], alone.bodies()[0]);
+    assert_close(*simulation.bodies()[1].pos(), Vector2D::new(100.0, 100.0), 1e-3);
+    assert!(simulation.bodies()[1].acceleration().is_none());
+}
+
+// The tree can't measure distances across the seam, so wrapping bounds switch Barnes-Hut back to the direct sum
+#[test]
+fn barnes_hut_uses_direct_sum_when_wrapping() {
+    // A cluster just across the seam from the first body, far enough away in the tree to be approximated as one
+    let bodies = std::iter::once(body([1.0, 50.0], [0.0, 0.0], 1.0))
+        .chain((0..20).map(|i| {
+            body(
+                [95.0 + (i % 4) as Scalar, 45.0 + (i / 4) as Scalar * 2.5],
+                [0.0, 0.0],
+                1.0,
+            )
+        }))
+        .collect();
+    let mut simulation = Simulation::new(bodies, None, None, None, CollisionMode::None);
+    simulation.set_bounds(Some(Bounds::new(Vector2D::new(0.0, 0.0), Vector2D::new(100.0, 100.0))));
+    simulation.set_gravity_mode(GravityMode::BarnesHut { theta: 0.5 });
+
+    simulation.set_boundary_mode(BoundaryMode::Bounce);
+    assert_eq!(simulation.net_forces(), simulation.barnes_hut_forces(0.5));
+    simulation.set_boundary_mode(BoundaryMode::Wrap);
+    assert_eq!(simulation.net_forces(), simulation.direct_forces());
+    assert_ne!(simulation.net_forces(), simulation.barnes_hut_forces(0.5));
+
+    // Pulled towards the cluster across the seam
+    simulation.gravity_tick(1.0);
+    assert!(simulation.bodies()[0].velocity().0.x < 0.0);
+}
@@ -0,0 +1,137 @@
+// Integration tests for the settings, run with `cargo test` or `cargo test --test settings` for only these
+#![cfg(feature = "native")]
+
+use frame_buffer::graphics::ThemeName;
+use frame_buffer::settings::{Args, OrNone, Settings};
+use frame_buffer::simulation::{CollisionMode, Integrator};
+
+use clap::error::ErrorKind;
+use clap::Parser;
+
+fn parse(args: &[&str]) -> Result<Args, clap::Error> {
+    Args::try_parse_from(std::iter::once("frame_buffer").chain(args.iter().copied()))
+}
+
+// ----------------------------------------------------------------
+
+#[test]
+fn args_reject_invalid_values() {
+    assert!(parse(&["--width", "0"]).is_err());
+    assert!(parse(&["--height", "-3"]).is_err());
+    assert!(parse(&["--bodies", "-3"]).is_err());
+    assert!(parse(&["--grav-const", "5000"]).is_err());
+    assert!(parse(&["--restitution", "1.5"]).is_err());
+    assert!(parse(&["--collision-mode", "Bounce"]).is_err());
+    assert!(parse(&["--background", "255,0"]).is_err());
+}
+
+#[test]
+fn args_accept_valid_values() {
+    let args = parse(&["--grav-const", "-0.5", "--seed", "none", "--bounds", "-10,-10,20,20"]).unwrap();
+    assert_eq!(args.grav_const, Some(-0.5));
+    assert_eq!(args.seed, Some(OrNone(None)));
+    assert!(args.bounds.is_some_and(|x| x.0.is_some()));
+}
+
+#[test]
+fn help_is_generated() {
+    assert_eq!(parse(&["--help"]).unwrap_err().kind(), ErrorKind::DisplayHelp);
+}
+
+#[test]
+fn apply_args_only_changes_given_settings() {
+    let mut settings = Settings::default();
+    settings
+        .apply_args(parse(&["--bodies", "25", "--seed", "7"]).unwrap())
+        .unwrap();
+
+    let default = Settings::default();
+    assert_eq!(settings.num_of_bodies, 25);
+    assert_eq!(settings.seed, Some(7));
+    assert_eq!(settings.width, default.width);
+    assert_eq!(settings.grav_const, default.grav_const);
+}
+
+#[test]
+fn config_round_trips_through_toml() {
+    let settings = Settings {
+        grav_const: 2.5,
+        physics_speed: 4,
+        collision_mode: CollisionMode::Absorb,
+        softening: 3.0,
+        integrator: Integrator::VelocityVerlet,
+        num_of_bodies: 12,
+        ..Settings::default()
+    };
+
+    let path = std::env::temp_dir().join("frame_buffer_round_trip.toml");
+    std::fs::write(&path, toml::to_string(&settings).unwrap()).unwrap();
+    assert_eq!(Settings::load_config(&path).unwrap(), settings);
+    std::fs::remove_file(&path).unwrap();
+}
+
+#[test]
+fn args_override_config() {
+    let path = std::env::temp_dir().join("frame_buffer_override.toml");
+    std::fs::write(&path, "grav_const = 2.5\nphysics_speed = 4\nnum_of_bodies = 3\n").unwrap();
+
+    let mut settings = Settings::default();
+    settings
+        .apply_args(parse(&["--physics-speed", "7", "--config", path.to_str().unwrap()]).unwrap())
+        .unwrap();
+    std::fs::remove_file(&path).unwrap();
+
+    assert_eq!(settings.grav_const, 2.5);
+    assert_eq!(settings.physics_speed, 7);
+    assert_eq!(settings.num_of_bodies, 3);
+}
+
+#[test]
+fn args_are_not_saved() {
+    let mut saved = Settings {
+        width: 800,
+        ..Settings::default()
+    };
+    let mut settings = saved.clone();
+    settings
+        .apply_args(parse(&["--bodies", "5", "--seed", "7", "--width", "300"]).unwrap())
+        .unwrap();
+    let initial = settings.clone();
+
+    // Changed while running
+    settings.show_grid = true;
+    settings.grav_const = 0.5;
+
+    saved.apply_changes(&initial, &settings);
+    let default = Settings::default();
+    assert_eq!(saved.num_of_bodies, default.num_of_bodies);
+    assert_eq!(saved.seed, None);
+    assert_eq!(saved.width, 800);
+    assert!(saved.show_grid);
+    assert_eq!(saved.grav_const, 0.5);
+}
+
+#[test]
+fn config_is_not_saved() {
+    let mut saved = Settings {
+        theme: ThemeName::Light,
+        width: 800,
+        ..Settings::default()
+    };
+    saved.keymap.insert("AddBody".to_string(), vec!["Y".to_string()]);
+    let path = std::env::temp_dir().join("frame_buffer_not_saved.toml");
+    std::fs::write(&path, "grav_const = 2.5\nnum_of_bodies = 3\n").unwrap();
+
+    let mut settings = saved.clone();
+    settings
+        .apply_args(parse(&["--config", path.to_str().unwrap()]).unwrap())
+        .unwrap();
+    std::fs::remove_file(&path).unwrap();
+    let initial = settings.clone();
+    // Changed while running
+    settings.bloom = true;
+
+    let before = saved.clone();
+    saved.apply_changes(&initial, &settings);
+    assert_eq!(saved, Settings { bloom: true, ..before });
+}